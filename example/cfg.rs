@@ -0,0 +1,11 @@
+mod a;
+#[cfg(test)]
+mod tests;
+#[cfg(feature = "local")]
+mod debug {
+    pub fn dump() {}
+}
+
+fn main() {
+    println!("{}", a::c::f());
+}
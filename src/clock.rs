@@ -0,0 +1,74 @@
+// Clock used by the poll loop, so it can be replaced in tests
+
+use std::time::{Duration, SystemTime};
+
+pub trait Clock {
+    fn now(&self) -> SystemTime;
+    fn sleep(&self, d: Duration);
+
+    /// Sleep until `t`, or return immediately if `t` has already passed.
+    fn sleep_until(&self, t: SystemTime) {
+        if let Ok(d) = t.duration_since(self.now()) {
+            self.sleep(d);
+        }
+    }
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+
+    fn sleep(&self, d: Duration) {
+        std::thread::sleep(d);
+    }
+}
+
+/// A clock which only advances when someone sleeps on it.
+#[cfg(test)]
+pub struct FakeClock(std::cell::Cell<SystemTime>);
+
+#[cfg(test)]
+impl FakeClock {
+    pub fn new() -> Self {
+        Self(std::cell::Cell::new(SystemTime::UNIX_EPOCH))
+    }
+
+    pub fn advance(&self, d: Duration) {
+        self.0.set(self.0.get() + d);
+    }
+}
+
+#[cfg(test)]
+impl Clock for FakeClock {
+    fn now(&self) -> SystemTime {
+        self.0.get()
+    }
+
+    fn sleep(&self, d: Duration) {
+        self.advance(d);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fake_clock_sleep_until() {
+        let clock = FakeClock::new();
+        let start = clock.now();
+        let next_try = start + Duration::new(5, 0);
+
+        clock.advance(Duration::new(2, 0));
+        clock.sleep_until(next_try);
+        assert_eq!(clock.now(), next_try);
+
+        // Deadline already passed, no sleep at all.
+        clock.advance(Duration::new(7, 0));
+        clock.sleep_until(next_try);
+        assert_eq!(clock.now(), start + Duration::new(12, 0));
+    }
+}
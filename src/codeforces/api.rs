@@ -0,0 +1,127 @@
+// Client side of the official Codeforces API, https://codeforces.com/apiHelp
+
+use error_chain::bail;
+use serde::Deserialize;
+use sha2::{Digest, Sha512};
+
+mod error {
+    error_chain::error_chain! {}
+}
+
+use error::*;
+
+pub struct Api {
+    key: String,
+    secret: String,
+}
+
+#[derive(Deserialize)]
+struct Envelope<T> {
+    status: String,
+    comment: Option<String>,
+    result: Option<T>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Submission {
+    pub id: u64,
+    pub contest_id: Option<u64>,
+    pub problem: Problem,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Problem {
+    pub index: String,
+}
+
+impl Api {
+    pub fn new(key: String, secret: String) -> Self {
+        Self { key, secret }
+    }
+
+    /// Make the path with the signed query string for calling `method`.
+    /// `rand` is 6 arbitrary characters and `time` is the UNIX time.
+    pub fn signed_path(
+        &self,
+        method: &str,
+        params: &[(&str, &str)],
+        time: u64,
+        rand: &str,
+    ) -> String {
+        let time = time.to_string();
+        let mut params: Vec<(&str, &str)> = params.to_vec();
+        params.push(("apiKey", &self.key));
+        params.push(("time", &time));
+        params.sort();
+
+        let query = url::form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(params)
+            .finish();
+        let hash = Sha512::digest(format!("{}/{}?{}#{}", rand, method, query, self.secret));
+        format!(
+            "/api/{}?{}&apiSig={}{}",
+            method,
+            query,
+            rand,
+            hex::encode(hash)
+        )
+    }
+}
+
+/// Parse the result of `user.status`, the submissions are the newest
+/// first.
+pub fn parse_user_status(json: &str) -> Result<Vec<Submission>> {
+    let env: Envelope<Vec<Submission>> =
+        serde_json::from_str(json).chain_err(|| "cannot parse API response")?;
+    if env.status != "OK" {
+        bail!(
+            "API call failed: {}",
+            env.comment.as_deref().unwrap_or("unknown error")
+        );
+    }
+    env.result.chain_err(|| "API response has no result")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signed_path() {
+        // The parameters of the example in https://codeforces.com/apiHelp.
+        let api = Api::new("xxx".to_owned(), "yyy".to_owned());
+        let path = api.signed_path(
+            "contest.hitList",
+            &[("contestId", "566")],
+            1234567890,
+            "123456",
+        );
+        assert_eq!(
+            path,
+            "/api/contest.hitList?apiKey=xxx&contestId=566&time=1234567890&apiSig=123456\
+             d7c6fb789db4d0571e1f0f3df4e9be2478a14171cdf075e65e1861ca377f028b\
+             c990994dc2a68cbbbd000a97be1f1699db7d4ce966915a87a10a2b021941e961"
+        );
+    }
+
+    #[test]
+    fn test_parse_user_status() {
+        let json = r#"{"status":"OK","result":[
+            {"id":98765432,"contestId":1234,"creationTimeSeconds":1,
+             "problem":{"contestId":1234,"index":"C","name":"Foo"},
+             "verdict":"OK"},
+            {"id":98765431,"contestId":1233,
+             "problem":{"contestId":1233,"index":"B1","name":"Bar"}}
+        ]}"#;
+        let v = parse_user_status(json).unwrap();
+        assert_eq!(v.len(), 2);
+        assert_eq!(v[0].id, 98765432);
+        assert_eq!(v[0].contest_id, Some(1234));
+        assert_eq!(v[1].problem.index, "B1");
+
+        let json = r#"{"status":"FAILED","comment":"handle: User not found"}"#;
+        let e = parse_user_status(json).unwrap_err();
+        assert!(e.to_string().contains("User not found"));
+    }
+}
@@ -1,15 +1,45 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 pub struct Config {
     pub server_url: Option<String>,
     pub identy: Option<String>,
     pub contest_path: Option<String>,
+    pub status_path: Option<String>,
+    pub status_query: Option<String>,
     pub user_agent: Option<String>,
     pub prefer_cxx: Option<String>,
     pub prefer_py: Option<String>,
     pub rust_edition: Option<String>,
+    pub prefer_kotlin: Option<String>,
+    pub prefer_csharp: Option<String>,
+    pub prefer_js: Option<String>,
+    pub prefer_java: Option<String>,
+    pub program_type_id: Option<u32>,
+    pub ext_map: Option<HashMap<String, String>>,
+    pub detect_cpp_in_c: Option<bool>,
     pub cookie_file: Option<std::path::PathBuf>,
     pub retry_limit: Option<i64>,
+    pub timeout_retries: Option<i64>,
+    pub retry_backoff: Option<u64>,
+    pub retry_budget: Option<i64>,
+    pub log_body_limit: Option<usize>,
     pub no_cookie: Option<bool>,
+    pub partial_ok: Option<bool>,
+    pub poll_after_submit: Option<bool>,
+    pub poll_interval: Option<u64>,
+    pub tab_size: Option<u32>,
+    pub webhook_url: Option<String>,
+    pub proxy: Option<String>,
+    pub http1: Option<bool>,
+    pub connect_timeout: Option<u64>,
+    pub timeout: Option<u64>,
+    pub totp_secret: Option<String>,
+    pub api_key: Option<String>,
+    pub api_secret: Option<String>,
+    pub rustfmt: Option<std::path::PathBuf>,
+    pub no_rustfmt: Option<bool>,
+    pub exit_codes: Option<HashMap<String, i32>>,
+    pub profiles: Option<HashMap<String, Config>>,
 }
@@ -1,4 +1,5 @@
 use error_chain::bail;
+use std::collections::HashMap;
 
 mod error {
     error_chain::error_chain! {}
@@ -7,9 +8,10 @@ mod error {
 use error::*;
 
 #[repr(u32)]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum Dialect {
     C = 43,
+    CXX23 = 91,
     CXX20 = 73,
     CXX17_64 = 61,
     CXX17 = 54,
@@ -18,75 +20,253 @@ pub enum Dialect {
     Python2 = 7,
     Pypy3 = 41,
     Pypy2 = 40,
+    Pypy3_64 = 70,
     Rust2021 = 75,
     Java = 36,
+    Java21 = 87,
+    Kotlin17 = 83,
+    Kotlin19 = 88,
+    Go = 32,
+    CSharpMono = 9,
+    CSharpDotnet = 79,
+    JavaScriptV8 = 34,
+    NodeJs = 55,
+    Scala = 20,
+}
+
+/// A table of dialects and their accepted names.
+pub type AliasTable = [(Dialect, &'static [&'static str])];
+
+/// Accepted names of C++ dialects, for `prefer_cxx` in the config file.
+pub const CXX_ALIASES: &AliasTable = &[
+    (
+        Dialect::CXX14,
+        &["c++14", "cxx14", "cpp14", "c++1y", "cxx1y", "cpp1y"],
+    ),
+    (
+        Dialect::CXX17,
+        &["c++17", "cxx17", "cpp17", "c++1z", "cxx1z", "cpp1z"],
+    ),
+    (
+        Dialect::CXX17_64,
+        &[
+            "c++17-64", "cxx17-64", "cpp17-64", "c++1z-64", "cxx1z-64", "cpp1z-64",
+        ],
+    ),
+    (
+        Dialect::CXX20,
+        &["c++20", "cxx20", "cpp20", "c++2a", "cxx2a", "cpp2a"],
+    ),
+    (
+        Dialect::CXX20,
+        &[
+            "c++20-64", "cxx20-64", "cpp20-64", "c++2a-64", "cxx2a-64", "cpp2a-64",
+        ],
+    ),
+    // Codeforces only has a 64-bit G++23.
+    (
+        Dialect::CXX23,
+        &["c++23", "cxx23", "cpp23", "c++2b", "cxx2b", "cpp2b"],
+    ),
+    (
+        Dialect::CXX23,
+        &[
+            "c++23-64", "cxx23-64", "cpp23-64", "c++2b-64", "cxx2b-64", "cpp2b-64",
+        ],
+    ),
+];
+
+/// Accepted names of Python dialects, for `prefer_py` in the config file.
+pub const PY_ALIASES: &AliasTable = &[
+    (Dialect::Python2, &["py2", "python2", "cpython2"]),
+    (Dialect::Python3, &["py3", "python3", "cpython3"]),
+    (Dialect::Pypy2, &["pypy2"]),
+    (Dialect::Pypy3, &["pypy3"]),
+    (Dialect::Pypy3_64, &["pypy3-64", "pypy3_64"]),
+];
+
+/// Accepted Rust editions, for `rust_edition` in the config file.
+pub const RS_ALIASES: &AliasTable = &[
+    (Dialect::Rust2021, &["2021"]),
+    // Codeforces only has a Rust 2021 compiler, older code is submitted
+    // with it.
+    (Dialect::Rust2021, &["2015", "2018"]),
+];
+
+/// Accepted Kotlin versions, for `prefer_kotlin` in the config file.
+pub const KT_ALIASES: &AliasTable = &[
+    (Dialect::Kotlin17, &["1.7", "kotlin1.7"]),
+    (Dialect::Kotlin19, &["1.9", "kotlin1.9"]),
+];
+
+/// Accepted C# compilers, for `prefer_csharp` in the config file.
+pub const CS_ALIASES: &AliasTable = &[
+    (Dialect::CSharpMono, &["mono"]),
+    (Dialect::CSharpDotnet, &["dotnet", ".net", "net6"]),
+];
+
+/// Accepted JavaScript engines, for `prefer_js` in the config file.
+pub const JS_ALIASES: &AliasTable = &[
+    (Dialect::JavaScriptV8, &["v8"]),
+    (Dialect::NodeJs, &["node", "nodejs", "node.js"]),
+];
+
+/// Accepted Java versions, for `prefer_java` in the config file.
+pub const JAVA_ALIASES: &AliasTable = &[
+    (Dialect::Java, &["java8", "8", "1.8"]),
+    (Dialect::Java21, &["java21", "21"]),
+];
+
+/// All the alias tables above, with the name of the language.
+pub const DIALECT_ALIASES: &[(&str, &AliasTable)] = &[
+    ("C++", CXX_ALIASES),
+    ("Python", PY_ALIASES),
+    ("Rust", RS_ALIASES),
+    ("Kotlin", KT_ALIASES),
+    ("C#", CS_ALIASES),
+    ("JavaScript", JS_ALIASES),
+    ("Java", JAVA_ALIASES),
+];
+
+/// Accepted names of all the dialects, for `--dialect`.
+pub const DIALECT_NAMES: &AliasTable = &[
+    (Dialect::C, &["c"]),
+    (Dialect::CXX23, &["c++23"]),
+    (Dialect::CXX20, &["c++20"]),
+    (Dialect::CXX17_64, &["c++17-64"]),
+    (Dialect::CXX17, &["c++17"]),
+    (Dialect::CXX14, &["c++14"]),
+    (Dialect::Python3, &["py3"]),
+    (Dialect::Python2, &["py2"]),
+    (Dialect::Pypy3, &["pypy3"]),
+    (Dialect::Pypy2, &["pypy2"]),
+    (Dialect::Pypy3_64, &["pypy3-64"]),
+    (Dialect::Rust2021, &["rust2021", "rust2018", "rust2015"]),
+    (Dialect::Java, &["java", "java8"]),
+    (Dialect::Java21, &["java21"]),
+    (Dialect::Kotlin19, &["kotlin", "kotlin1.9"]),
+    (Dialect::Kotlin17, &["kotlin1.7"]),
+    (Dialect::Go, &["go"]),
+    (Dialect::CSharpDotnet, &["csharp", "cs"]),
+    (Dialect::CSharpMono, &["csharp-mono", "cs-mono"]),
+    (Dialect::NodeJs, &["node", "js"]),
+    (Dialect::JavaScriptV8, &["v8", "js-v8"]),
+    (Dialect::Scala, &["scala"]),
+];
+
+fn recognize(table: &AliasTable, d: &str) -> Option<Dialect> {
+    table
+        .iter()
+        .find(|(_, aliases)| aliases.contains(&d))
+        .map(|(dialect, _)| *dialect)
 }
 
 pub fn cxx_dialect_recognize(d: &str) -> Result<Dialect> {
-    use Dialect::*;
-    Ok(match d {
-        "c++14" | "cxx14" | "cpp14" | "c++1y" | "cxx1y" | "cpp1y" => CXX14,
-        "c++17" | "cxx17" | "cpp17" | "c++1z" | "cxx1z" | "cpp1z" => CXX17,
-        "c++17-64" | "cxx17-64" | "cpp17-64" | "c++1z-64" | "cxx1z-64" | "cpp1z-64" => CXX17_64,
-        "c++20" | "cxx20" | "cpp20" | "c++2a" | "cxx2a" | "cpp2a" => CXX20,
-        "c++20-64" | "cxx20-64" | "cpp20-64" | "c++2a-64" | "cxx2a-64" | "cpp2a-64" => CXX20,
+    if let Some(dialect) = recognize(CXX_ALIASES, d) {
+        return Ok(dialect);
+    }
+    match d {
         "c++11" | "cxx11" | "cpp11" | "c++1x" | "cxx1x" | "cpp1x" => {
             bail!("C++11 support has been removed by Codeforces")
         }
         _ => bail!("unknown or unsupported C++ dialect: {}", d),
-    })
+    }
 }
 
 pub fn py_dialect_recognize(d: &str) -> Result<Dialect> {
-    use Dialect::*;
-    Ok(match d {
-        "py2" | "python2" | "cpython2" => Python2,
-        "py3" | "python3" | "cpython3" => Python3,
-        "pypy2" => Pypy2,
-        "pypy3" => Pypy3,
-        _ => bail!("unknown or unsupported Python dialect: {}", d),
-    })
+    recognize(PY_ALIASES, d).chain_err(|| format!("unknown or unsupported Python dialect: {}", d))
 }
 
 pub fn rs_edition_recognize(e: &str) -> Result<Dialect> {
-    Ok(match e {
-        "2021" => Dialect::Rust2021,
-        _ => bail!("unknown or unsupported Rust edition: {}", e),
-    })
+    recognize(RS_ALIASES, e).chain_err(|| format!("unknown or unsupported Rust edition: {}", e))
+}
+
+pub fn cs_compiler_recognize(c: &str) -> Result<Dialect> {
+    recognize(CS_ALIASES, c).chain_err(|| format!("unknown or unsupported C# compiler: {}", c))
+}
+
+pub fn js_engine_recognize(e: &str) -> Result<Dialect> {
+    recognize(JS_ALIASES, e)
+        .chain_err(|| format!("unknown or unsupported JavaScript engine: {}", e))
+}
+
+pub fn java_version_recognize(v: &str) -> Result<Dialect> {
+    recognize(JAVA_ALIASES, v).chain_err(|| format!("unknown or unsupported Java version: {}", v))
+}
+
+pub fn kt_version_recognize(v: &str) -> Result<Dialect> {
+    recognize(KT_ALIASES, v).chain_err(|| format!("unknown or unsupported Kotlin version: {}", v))
+}
+
+/// Parse a literal programTypeId, like `89` in `--dialect id:89`.  This
+/// allows using a compiler newly added by Codeforces before we know it.
+pub fn parse_type_id(s: &str) -> Result<u32> {
+    match s.parse::<u32>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => bail!("programTypeId must be a positive integer, not {}", s),
+    }
 }
 
 impl Dialect {
     pub fn new<S: AsRef<str>>(s: S) -> Result<Self> {
-        use Dialect::*;
-        Ok(match s.as_ref() {
-            "c" => C,
-            "c++20" => CXX20,
-            "c++17-64" => CXX17_64,
-            "c++17" => CXX17,
-            "c++14" => CXX14,
-            "py3" => Python3,
-            "py2" => Python2,
-            "pypy3" => Pypy3,
-            "pypy2" => Pypy2,
-            "rust2021" => Rust2021,
-            "java" => Java,
-            _ => bail!("don't know dialect {}", s.as_ref()),
-        })
+        recognize(DIALECT_NAMES, s.as_ref())
+            .chain_err(|| format!("don't know dialect {}", s.as_ref()))
+    }
+
+    /// Get the name of the dialect accepted by `--dialect`.
+    pub fn name(self) -> &'static str {
+        DIALECT_NAMES
+            .iter()
+            .find(|(d, _)| *d == self)
+            .map_or("unknown", |(_, names)| names[0])
     }
 
     pub fn to_id(self) -> String {
         (self as u32).to_string()
     }
 
+    /// Get the start of a line comment in the language.
+    pub fn line_comment(self) -> &'static str {
+        use Dialect::*;
+        match self {
+            Pypy2 | Python2 | Pypy3 | Pypy3_64 | Python3 => "#",
+            // All the other languages are C-like.
+            _ => "//",
+        }
+    }
+
+    /// Get a file extension for the language, used to name the source
+    /// read from stdin.
+    pub fn file_ext(self) -> &'static str {
+        use Dialect::*;
+        match self {
+            C => "c",
+            CXX14 | CXX17 | CXX17_64 | CXX20 | CXX23 => "cpp",
+            Pypy2 | Python2 | Pypy3 | Pypy3_64 | Python3 => "py",
+            Rust2021 => "rs",
+            Java | Java21 => "java",
+            Kotlin17 | Kotlin19 => "kt",
+            Go => "go",
+            CSharpMono | CSharpDotnet => "cs",
+            JavaScriptV8 | NodeJs => "js",
+            Scala => "scala",
+        }
+    }
+
     pub fn get_mime(self) -> &'static str {
         use Dialect::*;
         match self {
             C => "text/x-c++src",
-            CXX14 | CXX17 | CXX17_64 | CXX20 => "text/x-c++src",
+            CXX14 | CXX17 | CXX17_64 | CXX20 | CXX23 => "text/x-c++src",
             Pypy2 | Python2 => "text/x-python",
-            Pypy3 | Python3 => "text/x-python3",
+            Pypy3 | Pypy3_64 | Python3 => "text/x-python3",
             Rust2021 => "text/rust",
-            Java => "text/x-java",
+            Java | Java21 => "text/x-java",
+            Kotlin17 | Kotlin19 => "text/x-kotlin",
+            Go => "text/x-go",
+            CSharpMono | CSharpDotnet => "text/x-csharp",
+            JavaScriptV8 | NodeJs => "text/javascript",
+            Scala => "text/x-scala",
         }
     }
 }
@@ -95,29 +275,182 @@ pub struct DialectParser {
     cxx_dialect: Dialect,
     py_dialect: Dialect,
     rs_edition: Dialect,
+    // The Rust edition in the config file, if it's older than 2021.
+    rs_old_edition: Option<String>,
+    kt_version: Dialect,
+    cs_compiler: Dialect,
+    js_engine: Dialect,
+    java_version: Dialect,
+    // Extensions mapped by `ext_map` in the config file.
+    ext_map: HashMap<String, Dialect>,
+    detect_cpp_in_c: bool,
+}
+
+/// Things only in C++, for `detect_cpp_in_c` in the config file.
+const CXX_ONLY_TOKENS: &[&str] = &[
+    "<iostream>",
+    "std::",
+    "template<",
+    "template <",
+    "using namespace",
+];
+
+/// Check if the source code is C++ by looking for C++-only tokens.
+pub fn looks_like_cxx(src: &str) -> bool {
+    CXX_ONLY_TOKENS.iter().any(|t| src.contains(t))
 }
 
 impl DialectParser {
-    pub fn new<T: AsRef<str>, U: AsRef<str>, V: AsRef<str>>(
+    pub fn new<T, U, V, W, X, Y, Z>(
         cxx_dialect: T,
         py_dialect: U,
         rs_edition: V,
-    ) -> Result<Self> {
+        kt_version: W,
+        cs_compiler: X,
+        js_engine: Y,
+        java_version: Z,
+    ) -> Result<Self>
+    where
+        T: AsRef<str>,
+        U: AsRef<str>,
+        V: AsRef<str>,
+        W: AsRef<str>,
+        X: AsRef<str>,
+        Y: AsRef<str>,
+        Z: AsRef<str>,
+    {
         Ok(Self {
             cxx_dialect: cxx_dialect_recognize(cxx_dialect.as_ref())?,
             py_dialect: py_dialect_recognize(py_dialect.as_ref())?,
             rs_edition: rs_edition_recognize(rs_edition.as_ref())?,
+            rs_old_edition: Some(rs_edition.as_ref())
+                .filter(|e| *e != "2021")
+                .map(str::to_owned),
+            kt_version: kt_version_recognize(kt_version.as_ref())?,
+            cs_compiler: cs_compiler_recognize(cs_compiler.as_ref())?,
+            js_engine: js_engine_recognize(js_engine.as_ref())?,
+            java_version: java_version_recognize(java_version.as_ref())?,
+            ext_map: HashMap::new(),
+            detect_cpp_in_c: false,
         })
     }
 
+    /// Submit .c files looking like C++ with the C++ dialect.
+    pub fn set_detect_cpp_in_c(&mut self, value: bool) {
+        self.detect_cpp_in_c = value;
+    }
+
+    /// Like `get_lang_ext`, but `detect_cpp_in_c` may look into the
+    /// source code.
+    pub fn get_lang_file(&self, ext: &str, path: &std::path::Path) -> Result<Dialect> {
+        let d = self.get_lang_ext(ext)?;
+        if d != Dialect::C || !self.detect_cpp_in_c {
+            return Ok(d);
+        }
+        let src = std::fs::read_to_string(path)
+            .chain_err(|| format!("cannot load {}", path.display()))?;
+        if looks_like_cxx(&src) {
+            log::info!("{} looks like C++, using prefer_cxx", path.display());
+            return Ok(self.cxx_dialect);
+        }
+        Ok(d)
+    }
+
+    /// Map an extension to a dialect, overriding the builtin mapping.
+    pub fn map_ext(&mut self, ext: &str, dialect: &str) -> Result<()> {
+        let d = Dialect::new(dialect).chain_err(|| format!("bad dialect for .{}", ext))?;
+        self.ext_map.insert(ext.to_owned(), d);
+        Ok(())
+    }
+
+    /// Whether the extension is mapped with `map_ext`.
+    pub fn is_mapped(&self, ext: &str) -> bool {
+        self.ext_map.contains_key(ext)
+    }
+
+    /// Get the preferred dialects with their config keys.
+    pub fn preferred(&self) -> [(&'static str, Dialect); 7] {
+        [
+            ("prefer_cxx", self.cxx_dialect),
+            ("prefer_py", self.py_dialect),
+            ("rust_edition", self.rs_edition),
+            ("prefer_kotlin", self.kt_version),
+            ("prefer_csharp", self.cs_compiler),
+            ("prefer_js", self.js_engine),
+            ("prefer_java", self.java_version),
+        ]
+    }
+
+    /// Get the config key deciding the dialect for an extension, if any.
+    pub fn config_key(ext: &str) -> Option<&'static str> {
+        match ext {
+            "cc" | "cp" | "cxx" | "cpp" | "CPP" | "c++" | "C" => Some("prefer_cxx"),
+            "py" => Some("prefer_py"),
+            "rs" => Some("rust_edition"),
+            "kt" => Some("prefer_kotlin"),
+            "cs" => Some("prefer_csharp"),
+            // Only .js, TypeScript is not JavaScript.
+            "js" => Some("prefer_js"),
+            "java" => Some("prefer_java"),
+            _ => None,
+        }
+    }
+
     pub fn get_lang_ext(&self, ext: &str) -> Result<Dialect> {
+        if let Some(d) = self.ext_map.get(ext) {
+            return Ok(*d);
+        }
+        if let Some(key) = Self::config_key(ext) {
+            let (_, d) = self
+                .preferred()
+                .into_iter()
+                .find(|(k, _)| *k == key)
+                .expect("no preferred dialect for the config key");
+            if let (Some(e), "rust_edition") = (&self.rs_old_edition, key) {
+                log::warn!(
+                    "Codeforces only has Rust 2021, so Rust {} code is compiled as 2021 \
+                     and may not compile, e.g. with bare trait objects or `async` as an \
+                     identifier",
+                    e
+                );
+            }
+            return Ok(d);
+        }
         Ok(match ext {
             "c" => Dialect::C,
-            "cc" | "cp" | "cxx" | "cpp" | "CPP" | "c++" | "C" => self.cxx_dialect,
-            "py" => self.py_dialect,
-            "rs" => self.rs_edition,
-            "java" => Dialect::Java,
+            "go" => Dialect::Go,
+            // Codeforces only has one Scala compiler, so no prefer_scala.
+            "scala" => Dialect::Scala,
             _ => bail!("don't know extension {}", ext),
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_looks_like_cxx() {
+        assert!(looks_like_cxx("#include <iostream>\nint main() {}\n"));
+        assert!(looks_like_cxx("int main() { std::puts(\"\"); }\n"));
+        assert!(!looks_like_cxx("#include <stdio.h>\nint main() {}\n"));
+    }
+
+    #[test]
+    fn test_get_lang_ext() {
+        let p = DialectParser::new("c++17-64", "py3", "2021", "1.9", "dotnet", "node", "java8")
+            .unwrap();
+        for (key, d) in p.preferred() {
+            let exts = ["cpp", "cc", "py", "rs", "kt", "cs", "js", "java"];
+            for ext in exts
+                .iter()
+                .filter(|e| DialectParser::config_key(e) == Some(key))
+            {
+                assert!(p.get_lang_ext(ext).unwrap() == d);
+            }
+        }
+        assert!(p.get_lang_ext("c").unwrap() == Dialect::C);
+        assert!(p.get_lang_ext("ts").is_err());
+    }
+}
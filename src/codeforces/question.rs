@@ -0,0 +1,115 @@
+// Parsing the questions and answers (clarifications) of a contest
+
+use crate::unescape::Unescape;
+use regex::Regex;
+
+pub struct Clarification {
+    /// The problem index, or empty for the whole contest.
+    pub problem: String,
+    pub question: String,
+    pub answer: String,
+}
+
+/// Get the text in a table cell, without the tags and the extra spaces.
+fn cell_text(html: &str) -> String {
+    let br = Regex::new(r"(?i)<br\s*/?>").unwrap();
+    let tag = Regex::new(r"<[^>]*>").unwrap();
+    let txt = br.replace_all(html, "\n");
+    let txt = tag.replace_all(&txt, "").replace("&nbsp;", " ");
+    let txt = Unescape(&txt).to_string();
+    txt.lines()
+        .map(|l| l.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|l| !l.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Parse the clarifications from the questions page of the contest, the
+/// question and the answer are the last two columns of each row.  Only
+/// the rows in the questions table are parsed, not the ones in the
+/// sidebar.  Users not registered for the contest get an empty table,
+/// then the result is empty.
+pub fn parse_clarifications(txt: &str) -> Vec<Clarification> {
+    let table = Regex::new(
+        r#"(?s)<table[^>]*class="[^"]*problem-questions-table[^"]*"[^>]*>(.*?)</table>"#,
+    )
+    .unwrap();
+    let txt = match table.captures(txt) {
+        Some(caps) => caps.get(1).unwrap().as_str(),
+        None => return vec![],
+    };
+    let row = Regex::new(r"(?s)<tr[^>]*>(.*?)</tr>").unwrap();
+    let cell = Regex::new(r"(?s)<td[^>]*>(.*?)</td>").unwrap();
+    let problem = Regex::new(r#"href="[^"]*/problem/(?P<index>[A-Za-z0-9]+)""#).unwrap();
+    row.captures_iter(txt)
+        .filter_map(|r| {
+            let cells: Vec<&str> = cell
+                .captures_iter(&r[1])
+                .map(|c| c.get(1).unwrap().as_str())
+                .collect();
+            if cells.len() < 2 {
+                return None;
+            }
+            Some(Clarification {
+                problem: problem
+                    .captures(&r[1])
+                    .map_or_else(String::new, |x| x["index"].to_owned()),
+                question: cell_text(cells[cells.len() - 2]),
+                answer: cell_text(cells[cells.len() - 1]),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_clarifications() {
+        let txt = r#"<table class="problem-questions-table">
+<tr><th>#</th><th>Problem</th><th>When</th><th>Question</th><th>Answer</th></tr>
+<tr><td>1</td><td><a href="/contest/1234/problem/C">C</a></td><td>00:12</td>
+<td>Can n be 0?</td><td>No, read the statement.<br/>n &gt;= 1</td></tr>
+<tr><td>2</td><td></td><td>00:30</td><td>Is it rated?</td><td>Yes</td></tr>
+</table>"#;
+        let v = parse_clarifications(txt);
+        assert_eq!(v.len(), 2);
+        assert_eq!(v[0].problem, "C");
+        assert_eq!(v[0].question, "Can n be 0?");
+        assert_eq!(v[0].answer, "No, read the statement.\nn >= 1");
+        assert_eq!(v[1].problem, "");
+        assert_eq!(v[1].answer, "Yes");
+
+        let empty = r#"<table class="problem-questions-table">
+<tr><th>#</th><th>Question</th><th>Answer</th></tr></table>"#;
+        assert!(parse_clarifications(empty).is_empty());
+    }
+
+    #[test]
+    fn test_parse_clarifications_page() {
+        let txt = r#"<html><body><div id="sidebar">
+<div class="roundbox sidebox"><table class="rtable">
+<tr><th>#</th><th>User</th><th>Rating</th></tr>
+<tr><td>1</td><td><a href="/profile/tourist">tourist</a></td><td>3800</td></tr>
+</table></div>
+<div class="roundbox sidebox"><table class="rtable">
+<tr><td><a href="/contest/1234/problem/A">A</a></td><td>Accepted</td></tr>
+</table></div></div>
+<div id="pageContent"><div class="datatable"><table class="problem-questions-table">
+<tr><th>#</th><th>Problem</th><th>When</th><th>Question</th><th>Answer</th></tr>
+<tr><td>1</td><td><a href="/contest/1234/problem/B">B</a></td><td>00:05</td>
+<td>Is the graph connected?</td><td>Yes</td></tr>
+</table></div></div>
+<div id="footer"><table><tr><td>Codeforces</td><td>Copyright</td></tr></table></div>
+</body></html>"#;
+        let v = parse_clarifications(txt);
+        assert_eq!(v.len(), 1);
+        assert_eq!(v[0].problem, "B");
+        assert_eq!(v[0].question, "Is the graph connected?");
+        assert_eq!(v[0].answer, "Yes");
+
+        // Not even the table, like a page we don't know.
+        assert!(parse_clarifications("<html></html>").is_empty());
+    }
+}
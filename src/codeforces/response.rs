@@ -1,4 +1,5 @@
 use reqwest::StatusCode;
+use std::time::Duration;
 use url::Url;
 
 mod error {
@@ -11,9 +12,23 @@ use error::*;
 pub enum Response {
     Content(String),
     Redirection(Url),
+    Forbidden {
+        body: String,
+        retry_after: Option<String>,
+    },
     Other(StatusCode),
 }
 
+/// Get the seconds to wait from the Retry-After header.  The HTTP date
+/// form is not supported.
+pub fn retry_after(resp: &reqwest::blocking::Response) -> Option<Duration> {
+    resp.headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|x| x.to_str().ok())
+        .and_then(|x| x.trim().parse().ok())
+        .map(Duration::from_secs)
+}
+
 impl TryFrom<reqwest::blocking::Response> for Response {
     type Error = Error;
     fn try_from(resp: reqwest::blocking::Response) -> Result<Response> {
@@ -35,6 +50,18 @@ impl TryFrom<reqwest::blocking::Response> for Response {
             ));
         }
 
+        if resp.status() == StatusCode::FORBIDDEN {
+            let retry_after = resp
+                .headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|x| x.to_str().ok())
+                .map(|x| x.to_owned());
+            return Ok(Self::Forbidden {
+                body: resp.text().chain_err(|| "cannot parse response body")?,
+                retry_after,
+            });
+        }
+
         Ok(Self::Other(resp.status()))
     }
 }
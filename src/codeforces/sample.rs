@@ -0,0 +1,84 @@
+// Parsing the sample tests from the problem statement page
+
+use crate::unescape::Unescape;
+use error_chain::bail;
+use regex::Regex;
+
+mod error {
+    error_chain::error_chain! {}
+}
+
+use error::*;
+
+pub struct Sample {
+    pub input: String,
+    pub output: String,
+}
+
+/// Get the text of a `<pre>` block in the statement.  Newer statements
+/// put each line of the input into a `<div>`, and older ones use `<br />`.
+fn pre_text(html: &str) -> String {
+    let br = Regex::new(r"(?i)<br\s*/?>|</div>").unwrap();
+    let tag = Regex::new(r"<[^>]*>").unwrap();
+    let txt = br.replace_all(html, "\n");
+    let txt = tag.replace_all(&txt, "");
+    let txt = Unescape(&txt).to_string();
+    // A newline just after <pre> is not a part of the content.
+    let txt = txt.strip_prefix('\n').unwrap_or(&txt);
+    format!("{}\n", txt.trim_end_matches('\n'))
+}
+
+/// Parse the sample inputs and outputs from the problem statement page,
+/// in the order they appear.
+pub fn parse_samples(txt: &str) -> Result<Vec<Sample>> {
+    let re =
+        Regex::new(r#"(?s)<div class="(?P<kind>input|output)">.*?<pre[^>]*>(?P<pre>.*?)</pre>"#)
+            .unwrap();
+    let mut inputs = vec![];
+    let mut outputs = vec![];
+    for caps in re.captures_iter(txt) {
+        let v = if &caps["kind"] == "input" {
+            &mut inputs
+        } else {
+            &mut outputs
+        };
+        v.push(pre_text(&caps["pre"]));
+    }
+    if inputs.is_empty() {
+        bail!("no sample tests found");
+    }
+    if inputs.len() != outputs.len() {
+        bail!(
+            "found {} sample inputs but {} outputs",
+            inputs.len(),
+            outputs.len()
+        );
+    }
+    Ok(inputs
+        .into_iter()
+        .zip(outputs)
+        .map(|(input, output)| Sample { input, output })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_samples() {
+        let html = r#"<div class="sample-tests"><div class="section-title">Examples</div>
+<div class="sample-test"><div class="input"><div class="title">Input</div><pre>
+<div class="test-example-line test-example-line-even test-example-line-0">2</div><div class="test-example-line test-example-line-odd test-example-line-1">1 &lt; 2</div></pre></div><div class="output"><div class="title">Output</div><pre>
+YES
+</pre></div><div class="input"><div class="title">Input</div><pre>3<br />a&amp;b<br /></pre></div><div class="output"><div class="title">Output</div><pre>NO</pre></div></div></div>"#;
+        let v = parse_samples(html).unwrap();
+        assert_eq!(v.len(), 2);
+        assert_eq!(v[0].input, "2\n1 < 2\n");
+        assert_eq!(v[0].output, "YES\n");
+        assert_eq!(v[1].input, "3\na&b\n");
+        assert_eq!(v[1].output, "NO\n");
+
+        assert!(parse_samples("<html></html>").is_err());
+    }
+}
@@ -6,8 +6,8 @@ use std::path::{Path, PathBuf};
 pub enum Error {
     #[error("cannot parse file: {0}")]
     Parse(syn::parse::Error),
-    #[error("input/output error: {0}")]
-    IO(std::io::Error),
+    #[error("cannot read {0}: {1}")]
+    IO(PathBuf, std::io::Error),
     #[error("multiple path attribute for a mod")]
     MultiplePathAttr,
     #[error("bad path attribute: expect {0}")]
@@ -16,15 +16,181 @@ pub enum Error {
     BadSrcPath(PathBuf),
     #[error("module found at both {0} and {1}")]
     AmbiguityModule(PathBuf, PathBuf),
-    #[error("rustfmt fail")]
-    Rustfmt,
+    #[error("rustfmt failed on the unfolded code: {0}")]
+    Rustfmt(String),
+    #[error("cannot run rustfmt: {0}")]
+    RustfmtIO(std::io::Error),
+    #[error("rustfmt is not found at {0}")]
+    RustfmtNotFound(PathBuf),
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
 
-fn unfold_rust_src_recursive<P: AsRef<Path>>(p: P, search_parent: bool) -> Result<syn::File> {
+/// Options of unfolding.
+#[derive(Default)]
+pub struct Options {
+    /// The conditions like "test" or `feature = "local"`, the modules
+    /// gated by `#[cfg(...)]` with one of them are dropped.  `cfg(test)`
+    /// is always dropped.
+    pub skip_cfg: Vec<String>,
+    /// Remove the doc comments.  The other comments are never kept.
+    pub strip_comments: bool,
+    /// The directory to cache the unfolded code in, `None` to disable the
+    /// cache.
+    pub cache_dir: Option<PathBuf>,
+    /// Don't format the unfolded code with rustfmt.
+    pub no_rustfmt: bool,
+    /// The rustfmt executable, `None` to find rustfmt in PATH.
+    pub rustfmt: Option<PathBuf>,
+}
+
+/// The cached unfolded code, valid if none of the files are changed.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CacheEntry {
+    // The SHA-256 of the content of each file included.
+    files: Vec<(PathBuf, String)>,
+    output: String,
+}
+
+/// Cache entries not written for this long are removed.
+const CACHE_MAX_AGE: std::time::Duration = std::time::Duration::from_secs(30 * 86400);
+
+fn content_hash(content: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+    hex::encode(Sha256::digest(content))
+}
+
+fn file_hash(p: &Path) -> Option<String> {
+    Some(content_hash(&std::fs::read(p).ok()?))
+}
+
+/// Get the path of the cache file for unfolding `p` with `opts`.
+fn cache_path(dir: &Path, p: &Path, opts: &Options) -> Option<PathBuf> {
+    use sha2::{Digest, Sha256};
+    let p = std::fs::canonicalize(p).ok()?;
+    let key = format!(
+        "{}\0{:?}\0{}\0{}\0{:?}",
+        p.display(),
+        opts.skip_cfg,
+        opts.strip_comments,
+        opts.no_rustfmt,
+        opts.rustfmt
+    );
+    Some(dir.join(format!("{}.json", hex::encode(Sha256::digest(key)))))
+}
+
+fn load_cache(path: &Path) -> Option<String> {
+    let entry: CacheEntry = serde_json::from_slice(&std::fs::read(path).ok()?).ok()?;
+    let fresh = entry
+        .files
+        .iter()
+        .all(|(p, hash)| file_hash(p).as_ref() == Some(hash));
+    fresh.then_some(entry.output)
+}
+
+/// Save the unfolded code, `files` are the files included with the hash
+/// of the content we've read.
+fn save_cache(path: &Path, files: Vec<(PathBuf, String)>, output: &str) -> std::io::Result<()> {
+    let entry = CacheEntry {
+        files,
+        output: output.to_owned(),
+    };
+    std::fs::write(path, serde_json::to_vec(&entry)?)
+}
+
+/// Remove the cache entries not written for `CACHE_MAX_AGE`, so the cache
+/// directory doesn't grow forever.
+fn prune_cache(dir: &Path) {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(x) => x,
+        Err(_) => return,
+    };
+    let now = std::time::SystemTime::now();
+    for e in entries.flatten() {
+        let p = e.path();
+        if p.extension().and_then(|x| x.to_str()) != Some("json") {
+            continue;
+        }
+        let old = e
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| now.duration_since(t).ok())
+            .is_some_and(|age| age > CACHE_MAX_AGE);
+        if old {
+            if let Err(e) = std::fs::remove_file(&p) {
+                log::debug!("cannot remove old cache {}: {}", p.display(), e);
+            }
+        }
+    }
+}
+
+/// Remove the doc attributes like `#[doc = "..."]` and `#![doc = "..."]`,
+/// which `///` and `//!` comments are turned into.
+fn strip_doc(tokens: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+    use proc_macro2::{Delimiter, Group, TokenTree};
+    let is_doc = |t: Option<&TokenTree>| match t {
+        Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Bracket => matches!(
+            g.stream().into_iter().next(),
+            Some(TokenTree::Ident(i)) if i == "doc"
+        ),
+        _ => false,
+    };
+
+    let tokens: Vec<TokenTree> = tokens.into_iter().collect();
+    let mut out = vec![];
+    let mut i = 0;
+    while i < tokens.len() {
+        if let TokenTree::Punct(p) = &tokens[i] {
+            if p.as_char() == '#' {
+                let bang =
+                    matches!(tokens.get(i + 1), Some(TokenTree::Punct(p)) if p.as_char() == '!');
+                let n = if bang { 2 } else { 1 };
+                if is_doc(tokens.get(i + n)) {
+                    i += n + 1;
+                    continue;
+                }
+            }
+        }
+        out.push(match &tokens[i] {
+            TokenTree::Group(g) => {
+                let mut new = Group::new(g.delimiter(), strip_doc(g.stream()));
+                new.set_span(g.span());
+                TokenTree::Group(new)
+            }
+            t => t.clone(),
+        });
+        i += 1;
+    }
+    out.into_iter().collect()
+}
+
+/// Check if a `mod` item is gated by a `#[cfg(...)]` we want to drop.
+fn is_skipped(attrs: &[syn::Attribute], opts: &Options) -> bool {
+    use proc_macro2::{Delimiter, TokenTree};
+    let strip = |s: &str| s.chars().filter(|c| !c.is_whitespace()).collect::<String>();
+    attrs
+        .iter()
+        .filter(|a| a.path.is_ident("cfg"))
+        .filter_map(|a| match a.tokens.clone().into_iter().next() {
+            Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Parenthesis => {
+                Some(strip(&g.stream().to_string()))
+            }
+            _ => None,
+        })
+        .any(|cond| cond == "test" || opts.skip_cfg.iter().any(|x| strip(x) == cond))
+}
+
+/// Unfold the modules of `p`, and record the files included into `files`
+/// with the hash of their content.
+fn unfold_rust_src_recursive<P: AsRef<Path>>(
+    p: P,
+    search_parent: bool,
+    opts: &Options,
+    files: &mut Vec<(PathBuf, String)>,
+) -> Result<syn::File> {
     let p = PathBuf::from(p.as_ref());
-    let mut file = std::fs::File::open(&p).map_err(Error::IO)?;
+    let mut file = std::fs::File::open(&p).map_err(|e| Error::IO(p.clone(), e))?;
     let mut content = String::new();
     let parent = p
         .parent()
@@ -32,7 +198,13 @@ fn unfold_rust_src_recursive<P: AsRef<Path>>(p: P, search_parent: bool) -> Resul
     let mut recursive_sp = false;
 
     use std::io::Read;
-    file.read_to_string(&mut content).map_err(Error::IO)?;
+    file.read_to_string(&mut content)
+        .map_err(|e| Error::IO(p.clone(), e))?;
+    // Hash what we've read, the file may be changed after that.
+    files.push((
+        std::fs::canonicalize(&p).unwrap_or_else(|_| p.clone()),
+        content_hash(content.as_bytes()),
+    ));
     let mut ast = syn::parse_file(&content).map_err(Error::Parse)?;
 
     let mut items = vec![];
@@ -40,6 +212,14 @@ fn unfold_rust_src_recursive<P: AsRef<Path>>(p: P, search_parent: bool) -> Resul
 
     for mut item in items {
         if let syn::Item::Mod(m) = &mut item {
+            if is_skipped(&m.attrs, opts) {
+                continue;
+            }
+            if m.content.is_some() {
+                ast.items.push(item);
+                continue;
+            }
+
             let mut path_attr_idx = None;
             for i in 0..m.attrs.len() {
                 if m.attrs[i].path.is_ident("path") {
@@ -105,7 +285,7 @@ fn unfold_rust_src_recursive<P: AsRef<Path>>(p: P, search_parent: bool) -> Resul
                     Ok(p2)
                 })?;
 
-            let mod_file = unfold_rust_src_recursive(mod_path, recursive_sp)?;
+            let mod_file = unfold_rust_src_recursive(mod_path, recursive_sp, opts, files)?;
             use syn::token::Brace;
             m.content = Some((Brace::default(), mod_file.items));
         }
@@ -116,33 +296,88 @@ fn unfold_rust_src_recursive<P: AsRef<Path>>(p: P, search_parent: bool) -> Resul
     Ok(ast)
 }
 
-fn run_rustfmt(content: &str) -> Result<String> {
+fn run_rustfmt(content: &str, rustfmt: &Path) -> Result<String> {
     use std::process::{Command, Stdio};
-    use Error::Rustfmt;
     let content = content.to_owned();
 
-    let mut rustfmt = Command::new("rustfmt")
+    let mut child = Command::new(rustfmt)
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
         .spawn()
-        .map_err(|_| Rustfmt)?;
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => Error::RustfmtNotFound(rustfmt.to_owned()),
+            _ => Error::RustfmtIO(e),
+        })?;
 
-    let mut stdin = rustfmt.stdin.take().ok_or(Rustfmt)?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
     std::thread::spawn(move || {
         use std::io::Write;
-        stdin.write_all(content.as_bytes()).unwrap();
+        // rustfmt may exit early on bad input, then we get the error from
+        // its status.
+        let _ = stdin.write_all(content.as_bytes());
     });
 
-    let output = rustfmt.wait_with_output().map_err(|_| Rustfmt)?;
-    String::from_utf8(output.stdout).map_err(|_| Rustfmt)
+    let output = child.wait_with_output().map_err(Error::RustfmtIO)?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let msg = stderr.lines().find(|l| !l.trim().is_empty());
+        return Err(Error::Rustfmt(
+            msg.unwrap_or("exited with failure").trim().to_owned(),
+        ));
+    }
+    String::from_utf8(output.stdout)
+        .map_err(|_| Error::Rustfmt("the output is not valid UTF-8".to_owned()))
 }
 
-pub fn unfold_rust<P: AsRef<Path>>(p: P) -> Result<String> {
-    unfold_rust_src_recursive(p, true).map(|ast| {
-        use quote::ToTokens;
-        let content = ast.into_token_stream().to_string();
-        run_rustfmt(&content).unwrap_or(content)
-    })
+pub fn unfold_rust<P: AsRef<Path>>(p: P, opts: &Options) -> Result<String> {
+    let cache = opts
+        .cache_dir
+        .as_ref()
+        .and_then(|d| cache_path(d, p.as_ref(), opts));
+    if let Some(output) = cache.as_deref().and_then(load_cache) {
+        log::info!("none of the files changed, using the cached unfolded code");
+        return Ok(output);
+    }
+
+    let mut files = vec![];
+    let ast = unfold_rust_src_recursive(p, true, opts, &mut files)?;
+    use quote::ToTokens;
+    let mut tokens = ast.into_token_stream();
+    if opts.strip_comments {
+        tokens = strip_doc(tokens);
+    }
+    let content = tokens.to_string();
+    let output = if opts.no_rustfmt {
+        content
+    } else {
+        let rustfmt = opts.rustfmt.as_deref().unwrap_or(Path::new("rustfmt"));
+        match run_rustfmt(&content, rustfmt) {
+            Ok(output) => output,
+            Err(e) => {
+                match e {
+                    Error::RustfmtNotFound(_) => log::warn!(
+                        "{}, install it for prettier output or use \
+                         --no-rustfmt; using raw tokens",
+                        e
+                    ),
+                    e => log::warn!("{}, using raw tokens", e),
+                }
+                // Not cached, so it's formatted once rustfmt works.
+                return Ok(content);
+            }
+        }
+    };
+
+    if let Some(path) = &cache {
+        if let Err(e) = save_cache(path, files, &output) {
+            log::warn!("cannot save unfolded code to {}: {}", path.display(), e);
+        }
+        if let Some(dir) = &opts.cache_dir {
+            prune_cache(dir);
+        }
+    }
+    Ok(output)
 }
 
 #[cfg(test)]
@@ -151,7 +386,7 @@ mod tests {
 
     #[test]
     fn test_unfold_rust() {
-        let x = unfold_rust("example/t.rs").unwrap();
+        let x = unfold_rust("example/t.rs", &Options::default()).unwrap();
         assert_eq!(
             x,
             "mod a {
@@ -174,4 +409,130 @@ fn main() {
 "
         );
     }
+
+    #[test]
+    fn test_unfold_rust_cfg() {
+        let opts = Options {
+            skip_cfg: vec!["feature = \"local\"".to_owned()],
+            ..Options::default()
+        };
+        let x = unfold_rust("example/cfg.rs", &opts).unwrap();
+        assert_eq!(
+            x,
+            "mod a {
+    pub mod c {
+        pub fn f() -> i32 {
+            42
+        }
+    }
+}
+fn main() {
+    println!(\"{}\", a::c::f());
+}
+"
+        );
+    }
+
+    #[test]
+    fn test_unfold_cache() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let cache_dir = dir.join("cache");
+        std::fs::create_dir_all(&cache_dir).unwrap();
+        let main = dir.join("main.rs");
+        std::fs::write(&main, "mod a;\nfn main() {}\n").unwrap();
+        std::fs::write(dir.join("a.rs"), "pub fn f() {}\n").unwrap();
+        let opts = Options {
+            cache_dir: Some(cache_dir),
+            ..Options::default()
+        };
+
+        let x = unfold_rust(&main, &opts).unwrap();
+        assert!(x.contains("pub fn f()"));
+
+        // Make sure the cached code is used.
+        let path = cache_path(opts.cache_dir.as_ref().unwrap(), &main, &opts).unwrap();
+        let mut entry: CacheEntry = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        entry.output = "cached".to_owned();
+        std::fs::write(&path, serde_json::to_vec(&entry).unwrap()).unwrap();
+        assert_eq!(unfold_rust(&main, &opts).unwrap(), "cached");
+
+        // Changing an included file invalidates the cache.
+        std::fs::write(dir.join("a.rs"), "pub fn g() {}\n").unwrap();
+        let x = unfold_rust(&main, &opts).unwrap();
+        assert!(x.contains("pub fn g()"));
+    }
+
+    #[test]
+    fn test_cache_path() {
+        let dir = Path::new("/tmp/cache");
+        let opts = Options::default();
+        let other = Options {
+            rustfmt: Some(PathBuf::from("/opt/rust/bin/rustfmt")),
+            ..Options::default()
+        };
+        let p = Path::new("example/t.rs");
+        assert_ne!(cache_path(dir, p, &opts), cache_path(dir, p, &other));
+    }
+
+    #[test]
+    fn test_prune_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = dir.path().join("old.json");
+        let new = dir.path().join("new.json");
+        let other = dir.path().join("README");
+        for p in [&old, &new, &other] {
+            std::fs::write(p, "{}").unwrap();
+        }
+        let long_ago = std::time::SystemTime::now() - CACHE_MAX_AGE * 2;
+        for p in [&old, &other] {
+            let f = std::fs::File::options().write(true).open(p).unwrap();
+            f.set_modified(long_ago).unwrap();
+        }
+        prune_cache(dir.path());
+        assert!(!old.exists());
+        assert!(new.exists());
+        assert!(other.exists());
+    }
+
+    #[test]
+    fn test_missing_module() {
+        let dir = tempfile::tempdir().unwrap();
+        let main = dir.path().join("main.rs");
+        std::fs::write(&main, "mod gone;\nfn main() {}\n").unwrap();
+        let e = unfold_rust(&main, &Options::default()).unwrap_err();
+        assert!(e.to_string().contains("gone.rs"), "{}", e);
+    }
+
+    #[test]
+    fn test_rustfmt() {
+        let e = run_rustfmt("fn main() {}", Path::new("/nonexistent/rustfmt")).unwrap_err();
+        assert!(matches!(e, Error::RustfmtNotFound(_)));
+        // rustfmt may not be installed, like in a minimal CI image.
+        let has_rustfmt = std::process::Command::new("rustfmt")
+            .arg("--version")
+            .output()
+            .is_ok_and(|x| x.status.success());
+        if has_rustfmt {
+            let e = run_rustfmt("fn main() {", Path::new("rustfmt")).unwrap_err();
+            assert!(matches!(e, Error::Rustfmt(_)));
+        }
+
+        let opts = Options {
+            no_rustfmt: true,
+            rustfmt: Some(PathBuf::from("/nonexistent/rustfmt")),
+            ..Options::default()
+        };
+        let x = unfold_rust("example/a/c.rs", &opts).unwrap();
+        assert!(!x.contains('\n'));
+    }
+
+    #[test]
+    fn test_strip_doc() {
+        let src: proc_macro2::TokenStream = "#![doc = \"crate\"] /// Doc.\n\
+            #[inline] fn f() { //! Inner.\n 1 }"
+            .parse()
+            .unwrap();
+        assert_eq!(strip_doc(src).to_string(), "# [inline] fn f () { 1 }");
+    }
 }
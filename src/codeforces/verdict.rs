@@ -1,4 +1,8 @@
-use serde_aux::field_attributes::deserialize_bool_from_anything;
+use crate::unescape::Unescape;
+use error_chain::bail;
+use serde_aux::field_attributes::{
+    deserialize_bool_from_anything, deserialize_option_number_from_string,
+};
 
 mod error {
     error_chain::error_chain! {}
@@ -11,15 +15,28 @@ pub enum VerdictCode {
     Rejected,
     Waiting,
     CompilationError,
+    Partial,
+    Skipped,
+    Hacked,
 }
 
 pub struct Verdict {
     code: VerdictCode,
     msg: String,
+    time_ms: Option<u64>,
+    memory_bytes: Option<u64>,
+    passed_tests: Option<u32>,
+    // The test running now, if it's being judged.
+    running_test: Option<u32>,
 }
 
-pub fn parse_submission_id(txt: &str) -> Result<String> {
+/// Parse the ID of the last submission from the status page.  Return
+/// `None` if the status table is there but it has no submissions.
+pub fn parse_submission_id(txt: &str) -> Result<Option<String>> {
     use regex::Regex;
+    if txt.contains("status-frame-datatable") && !txt.contains("status-verdict-cell") {
+        return Ok(None);
+    }
     let re = Regex::new(
         r"<td party[^>]* class=[^>]*status-verdict-cell.*submissionId=.(?P<id>[0-9]*).*\n",
     )
@@ -27,7 +44,104 @@ pub fn parse_submission_id(txt: &str) -> Result<String> {
     let caps = re
         .captures(txt)
         .chain_err(|| "no match for submission ID")?;
-    Ok(caps["id"].to_owned())
+    Ok(Some(caps["id"].to_owned()))
+}
+
+/// Parse the problem index of the last submission from the status page.
+/// Return `None` if the status table is there but it has no submissions.
+pub fn parse_submission_problem(txt: &str) -> Result<Option<String>> {
+    use regex::Regex;
+    if txt.contains("status-frame-datatable") && !txt.contains("status-verdict-cell") {
+        return Ok(None);
+    }
+    let verdict = Regex::new(r"<td party[^>]* class=[^>]*status-verdict-cell")
+        .unwrap()
+        .find(txt)
+        .chain_err(|| "no match for submission")?;
+    // The problem cell is in the same row, before the verdict cell.
+    let re = Regex::new(r#"href="[^"]*/problem/(?P<index>[A-Za-z0-9]+)""#).unwrap();
+    let caps = re
+        .captures_iter(&txt[..verdict.start()])
+        .last()
+        .chain_err(|| "no match for problem index")?;
+    Ok(Some(caps["index"].to_owned()))
+}
+
+/// A row of the status table, the texts are as shown on the page.
+pub struct SubmissionRow {
+    pub id: String,
+    pub problem: String,
+    pub verdict: String,
+    pub time: String,
+    pub memory: String,
+}
+
+/// Get the text of the first table cell with `class` in `row`, without
+/// the tags.
+fn cell_text(row: &str, class: &str) -> String {
+    use regex::Regex;
+    let re = Regex::new(&format!(
+        r"(?s)<td[^>]*class=[^>]*{}[^>]*>(.*?)</td>",
+        class
+    ))
+    .unwrap();
+    let html = match re.captures(row) {
+        Some(caps) => caps.get(1).map_or("", |x| x.as_str()).to_owned(),
+        None => return String::new(),
+    };
+    let txt = Regex::new(r"<[^>]*>")
+        .unwrap()
+        .replace_all(&html, "")
+        .replace("&nbsp;", " ");
+    let txt = Unescape(&txt).to_string();
+    txt.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Parse at most `n` submissions from the status page, the newest first.
+pub fn parse_submission_rows(txt: &str, n: usize) -> Result<Vec<SubmissionRow>> {
+    use regex::Regex;
+    if txt.contains("status-frame-datatable") && !txt.contains("status-verdict-cell") {
+        return Ok(vec![]);
+    }
+    let re = Regex::new(r#"<tr[^>]* data-submission-id="(?P<id>[0-9]+)""#).unwrap();
+    let starts: Vec<_> = re.captures_iter(txt).collect();
+    if starts.is_empty() {
+        bail!("no match for submission rows");
+    }
+    let problem = Regex::new(r#"href="[^"]*/problem/(?P<index>[A-Za-z0-9]+)""#).unwrap();
+    let mut rows = vec![];
+    for (i, caps) in starts.iter().enumerate().take(n) {
+        let begin = caps.get(0).unwrap().start();
+        let end = starts
+            .get(i + 1)
+            .map_or(txt.len(), |x| x.get(0).unwrap().start());
+        let row = &txt[begin..end];
+        rows.push(SubmissionRow {
+            id: caps["id"].to_owned(),
+            problem: problem
+                .captures(row)
+                .map_or_else(String::new, |x| x["index"].to_owned()),
+            verdict: cell_text(row, "status-verdict-cell"),
+            time: cell_text(row, "time-consumed-cell"),
+            memory: cell_text(row, "memory-consumed-cell"),
+        });
+    }
+    Ok(rows)
+}
+
+/// Check if the JSON is an error envelope instead of a verdict, which
+/// Codeforces sends when it's overloaded.  Return the error message if so.
+pub fn unavailable_message(json: &str) -> Option<String> {
+    let value: serde_json::Value = serde_json::from_str(json).ok()?;
+    let obj = value.as_object()?;
+    if obj.contains_key("verdict") {
+        return None;
+    }
+    let msg = ["message", "error", "comment"]
+        .iter()
+        .find_map(|k| obj.get(*k).and_then(|x| x.as_str()))
+        .unwrap_or("unexpected response");
+    Some(msg.to_owned())
 }
 
 #[derive(serde::Deserialize)]
@@ -38,6 +152,87 @@ pub struct VerdictJson {
     verdict: String,
     #[serde(deserialize_with = "deserialize_bool_from_anything")]
     waiting: bool,
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    test_number: Option<u32>,
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    time_consumed_millis: Option<u64>,
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    memory_consumed_bytes: Option<u64>,
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    passed_test_count: Option<u32>,
+}
+
+/// The JSON object printed for each verdict with `--format json`.  The
+/// fields are stable, scripts and editor plugins depend on them.
+#[derive(serde::Serialize)]
+pub struct VerdictReport<'a> {
+    /// The submission ID.
+    pub id: &'a str,
+    /// The verdict name in the same form as the Codeforces API, like
+    /// `WRONG_ANSWER`, or `TESTING` if it's still being judged.
+    pub verdict: &'static str,
+    /// The message shown by Codeforces, like `Wrong answer on test 7`.
+    pub message: &'a str,
+    /// The number of passed tests, if known.
+    pub passed: Option<u32>,
+    /// The time consumed in milliseconds, if known.
+    pub time_ms: Option<u64>,
+    /// The memory consumed in KiB, if known.
+    pub memory_kb: Option<u64>,
+    /// The judgement protocol, only for compilation errors (the compiler
+    /// output) or with `--protocol`.
+    pub protocol: Option<&'a str>,
+}
+
+/// Format the time consumed by a submission.  We build the string
+/// ourselves so it's always the same regardless of the user's locale,
+/// and easy for scripts to parse.
+pub fn format_time(ms: u64) -> String {
+    format!("{} ms", ms)
+}
+
+/// Format the memory consumed by a submission, always using '.' as the
+/// decimal point and one digit after it.
+pub fn format_memory(bytes: u64) -> String {
+    let tenths = (bytes * 10 + 512 * 1024) / (1024 * 1024);
+    if tenths < 10 {
+        format!("{} KB", (bytes + 512) / 1024)
+    } else {
+        format!("{}.{} MB", tenths / 10, tenths % 10)
+    }
+}
+
+/// Get the checker comment of the last test in the judgement protocol,
+/// like "1st numbers differ - expected: '5', found: '4'".  The "wrong
+/// answer" prefix of the checker is removed.  Return `None` if there is no
+/// comment.
+pub fn parse_checker_comment(protocol: &str) -> Option<String> {
+    let (_, log) = protocol.rsplit_once("Checker Log")?;
+    let comment = log
+        .lines()
+        .skip(1)
+        .take_while(|l| !l.trim().is_empty() && !l.starts_with("Test: #"))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let comment = comment.trim();
+    let comment = match comment.get(..12) {
+        Some(p) if p.eq_ignore_ascii_case("wrong answer") => comment[12..].trim_start(),
+        _ => comment,
+    };
+    if comment.is_empty() {
+        None
+    } else {
+        Some(comment.to_owned())
+    }
+}
+
+/// Make the message for a running submission.  The test number may be
+/// absent or zero early in judging, then we just say "Running".
+fn running_msg(test_number: Option<u32>) -> String {
+    match test_number {
+        Some(n) => format!("Running on test {}", n),
+        None => "Running".to_owned(),
+    }
 }
 
 impl Verdict {
@@ -45,9 +240,19 @@ impl Verdict {
         Verdict {
             code,
             msg: msg.to_string(),
+            time_ms: None,
+            memory_bytes: None,
+            passed_tests: None,
+            running_test: None,
         }
     }
 
+    /// A verdict for a submission still waiting to be judged, when we
+    /// can't get the real one.
+    pub fn waiting<T: ToString>(msg: T) -> Self {
+        Verdict::new(VerdictCode::Waiting, msg)
+    }
+
     pub fn from_json(json: &str) -> Result<Self> {
         use regex::Regex;
         use VerdictCode::*;
@@ -55,27 +260,67 @@ impl Verdict {
         let verdict_json: VerdictJson =
             serde_json::from_str(json).chain_err(|| "can not parse JSON")?;
 
-        // Remove HTML labels like <span> from message
+        // Remove HTML labels like <span> from message, and decode the
+        // entities like &gt; in the checker comment.
         let re = Regex::new(r"<.[^>]*>").unwrap();
-        let msg = re.replace_all(&verdict_json.verdict, "");
+        let msg = Unescape(&re.replace_all(&verdict_json.verdict, "")).to_string();
 
         if verdict_json.compilation_error {
             return Ok(Verdict::new(CompilationError, msg));
         }
 
         if verdict_json.waiting {
-            return Ok(Verdict::new(Waiting, msg));
+            if !msg.starts_with("Running") {
+                return Ok(Verdict::new(Waiting, msg));
+            }
+            let test = verdict_json.test_number.filter(|&n| n > 0);
+            return Ok(Verdict {
+                running_test: test,
+                ..Verdict::new(Waiting, running_msg(test))
+            });
         }
 
-        if verdict_json.verdict.contains("verdict-accepted") {
-            return Ok(Verdict::new(Accepted, msg));
-        }
+        let passed = verdict_json.passed_test_count;
+        let code = if verdict_json.verdict.contains("verdict-accepted") {
+            Accepted
+        } else if msg.starts_with("Partial") {
+            // Partial results in scoring contests.
+            Partial
+        } else if verdict_json.verdict.contains("verdict-hacked") || msg.starts_with("Hacked") {
+            // Successfully hacked during the hacking phase.
+            Hacked
+        } else if msg.starts_with("Skipped") {
+            // Submissions skipped by the system, usually because a later
+            // submission to the same problem is judged instead.
+            Skipped
+        } else {
+            Rejected
+        };
+
+        // The message usually says which test failed, but not always.
+        let msg = match (&code, passed) {
+            (Rejected, Some(n)) if !msg.contains(" on test ") => {
+                format!("{} on test {}", msg, n + 1)
+            }
+            _ => msg,
+        };
 
-        Ok(Verdict::new(Rejected, msg))
+        Ok(Verdict {
+            passed_tests: passed,
+            time_ms: verdict_json.time_consumed_millis,
+            memory_bytes: verdict_json.memory_consumed_bytes,
+            ..Verdict::new(code, msg)
+        })
     }
 
-    pub fn print<W: termcolor::WriteColor>(&self, w: &mut W) -> std::io::Result<()> {
-        use termcolor::Color::{Green, Red};
+    /// Print the verdict.  If `partial_ok` is true, partial results are
+    /// printed without the red color.
+    pub fn print<W: termcolor::WriteColor>(
+        &self,
+        w: &mut W,
+        partial_ok: bool,
+    ) -> std::io::Result<()> {
+        use termcolor::Color::{Green, Magenta, Red, Yellow};
         use termcolor::ColorSpec;
         use VerdictCode::*;
         let use_color = w.supports_color();
@@ -83,19 +328,134 @@ impl Verdict {
             let color = match &self.code {
                 Accepted => Some(Green),
                 Rejected | CompilationError => Some(Red),
-                Waiting => None,
+                Partial if !partial_ok => Some(Red),
+                Partial | Waiting => None,
+                Skipped => Some(Yellow),
+                Hacked => Some(Magenta),
             };
             w.set_color(ColorSpec::new().set_fg(color))?;
         }
 
         w.write_all(self.msg.as_bytes())?;
+        // Show the progress, it's updated in place while polling.
+        if self.running_test.is_some() {
+            w.write_all(b"...")?;
+        }
         if use_color {
             w.reset()?;
         }
-        w.write_all(b"\n")?;
+
+        // Only set for judged submissions, and may be absent even then.
+        if let (Accepted, Some(n)) = (&self.code, self.passed_tests) {
+            write!(w, "  {} tests", n)?;
+        }
+        if let Some(ms) = self.time_ms {
+            write!(w, "  {}", format_time(ms))?;
+        }
+        if let Some(bytes) = self.memory_bytes {
+            write!(w, "  {}", format_memory(bytes))?;
+        }
+
+        // It's easy to mistake it for a time limit exceeded.
+        if self.is_idleness() {
+            w.write_all(b"  (the program was idle, forgot to flush the output?)")?;
+        }
         Ok(())
     }
 
+    /// Make the report for the verdict of submission `id`.
+    pub fn report<'a>(&'a self, id: &'a str, protocol: Option<&'a str>) -> VerdictReport<'a> {
+        VerdictReport {
+            id,
+            verdict: self.name(),
+            message: &self.msg,
+            passed: self.passed_tests,
+            time_ms: self.time_ms,
+            memory_kb: self.memory_bytes.map(|x| x / 1024),
+            protocol,
+        }
+    }
+
+    /// Make the JSON report for the verdict of submission `id`.
+    pub fn to_json(&self, id: &str, protocol: Option<&str>) -> String {
+        // Serializing plain fields into a string can't fail.
+        serde_json::to_string(&self.report(id, protocol)).unwrap()
+    }
+
+    /// Get the message of the verdict, like `Wrong answer on test 7`.
+    pub fn message(&self) -> &str {
+        &self.msg
+    }
+
+    /// Get the name of the verdict, in the same form as the `verdict` field
+    /// in Codeforces API (for example `WRONG_ANSWER`).
+    pub fn name(&self) -> &'static str {
+        use VerdictCode::*;
+        match self.code {
+            Accepted => "OK",
+            CompilationError => "COMPILATION_ERROR",
+            Waiting => "TESTING",
+            Partial => "PARTIAL",
+            Skipped => "SKIPPED",
+            Hacked => "CHALLENGED",
+            Rejected => {
+                const NAMES: &[(&str, &str)] = &[
+                    ("Wrong answer", "WRONG_ANSWER"),
+                    ("Time limit exceeded", "TIME_LIMIT_EXCEEDED"),
+                    ("Memory limit exceeded", "MEMORY_LIMIT_EXCEEDED"),
+                    ("Idleness limit exceeded", "IDLENESS_LIMIT_EXCEEDED"),
+                    ("Runtime error", "RUNTIME_ERROR"),
+                    ("Presentation error", "PRESENTATION_ERROR"),
+                    ("Security violated", "SECURITY_VIOLATED"),
+                    ("Denial of judgement", "CRASHED"),
+                    ("Judgement failed", "FAILED"),
+                ];
+                NAMES
+                    .iter()
+                    .find(|(prefix, _)| self.msg.starts_with(prefix))
+                    .map_or("REJECTED", |(_, name)| name)
+            }
+        }
+    }
+
+    /// Get the default exit code for the verdict: 0 for accepted, 2 for
+    /// compilation error, 3 if still waiting, and 1 for anything else.
+    pub fn exit_code(&self) -> i32 {
+        match self.code {
+            VerdictCode::Accepted => 0,
+            VerdictCode::CompilationError => 2,
+            VerdictCode::Waiting => 3,
+            _ => 1,
+        }
+    }
+
+    /// Whether the submission is idle for too long, usually because an
+    /// interactive solution waits for input without flushing its output.
+    pub fn is_idleness(&self) -> bool {
+        matches!(self.code, VerdictCode::Rejected)
+            && self.msg.starts_with("Idleness limit exceeded")
+    }
+
+    pub fn is_wrong_answer(&self) -> bool {
+        self.name() == "WRONG_ANSWER"
+    }
+
+    /// Append the checker comment in the judgement protocol to the message
+    /// of a wrong answer, like `Wrong answer on test 3: 1st numbers
+    /// differ`.
+    pub fn add_checker_comment(&mut self, protocol: &str) {
+        if !self.is_wrong_answer() {
+            return;
+        }
+        if let Some(comment) = parse_checker_comment(protocol) {
+            self.msg = format!("{}: {}", self.msg, comment);
+        }
+    }
+
+    pub fn is_partial(&self) -> bool {
+        matches!(self.code, VerdictCode::Partial)
+    }
+
     pub fn is_waiting(&self) -> bool {
         matches!(self.code, VerdictCode::Waiting)
     }
@@ -104,3 +464,296 @@ impl Verdict {
         matches!(self.code, VerdictCode::CompilationError)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_submission_id_empty() {
+        let txt = "<table class=\"status-frame-datatable\">\n\
+                   <tr><th>#</th><th>When</th></tr>\n</table>\n";
+        assert!(parse_submission_id(txt).unwrap().is_none());
+        assert!(parse_submission_id("<html></html>").is_err());
+    }
+
+    #[test]
+    fn test_parse_submission_problem() {
+        let txt = "<table class=\"status-frame-datatable\">\n\
+                   <tr data-submission-id=\"98765432\">\n\
+                   <td class=\"status-small\" data-problemId=\"1\">\n\
+                   <a href=\"/contest/1234/problem/C1\">C1 - Easy</a></td>\n\
+                   <td party=\"1\" class=\"status-cell status-verdict-cell\" \
+                   submissionId=\"98765432\">Accepted</td>\n\
+                   <tr data-submission-id=\"98765431\">\n\
+                   <a href=\"/contest/1234/problem/B\">B - Old</a></td>\n\
+                   <td party=\"1\" class=\"status-cell status-verdict-cell\" \
+                   submissionId=\"98765431\">Accepted</td>\n</table>\n";
+        assert_eq!(parse_submission_problem(txt).unwrap().unwrap(), "C1");
+        assert_eq!(parse_submission_id(txt).unwrap().unwrap(), "98765432");
+    }
+
+    #[test]
+    fn test_parse_submission_rows() {
+        let txt = "<table class=\"status-frame-datatable\">\n\
+                   <tr data-submission-id=\"98765432\" data-a=\"1\">\n\
+                   <td class=\"status-small\" data-problemId=\"1\">\n\
+                   <a href=\"/contest/1234/problem/C1\">C1 - Easy</a></td>\n\
+                   <td party=\"1\" class=\"status-cell status-verdict-cell\" \
+                   submissionId=\"98765432\"><span class='verdict-rejected'>\
+                   Wrong answer on test <span>3</span></span></td>\n\
+                   <td class=\"time-consumed-cell\">\n15&nbsp;ms\n</td>\n\
+                   <td class=\"memory-consumed-cell\">\n100&nbsp;KB\n</td>\n\
+                   </tr>\n\
+                   <tr data-submission-id=\"98765431\">\n\
+                   <a href=\"/contest/1234/problem/B\">B - Old</a></td>\n\
+                   <td party=\"1\" class=\"status-cell status-verdict-cell\" \
+                   submissionId=\"98765431\">Accepted</td>\n</tr>\n</table>\n";
+        let rows = parse_submission_rows(txt, 10).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].id, "98765432");
+        assert_eq!(rows[0].problem, "C1");
+        assert_eq!(rows[0].verdict, "Wrong answer on test 3");
+        assert_eq!(rows[0].time, "15 ms");
+        assert_eq!(rows[0].memory, "100 KB");
+        assert_eq!(rows[1].problem, "B");
+        assert_eq!(rows[1].verdict, "Accepted");
+        assert_eq!(rows[1].time, "");
+        assert_eq!(parse_submission_rows(txt, 1).unwrap().len(), 1);
+
+        let empty = "<table class=\"status-frame-datatable\"></table>";
+        assert!(parse_submission_rows(empty, 10).unwrap().is_empty());
+        assert!(parse_submission_rows("<html></html>", 10).is_err());
+    }
+
+    #[test]
+    fn test_unescape_message() {
+        let json = r#"{"compilationError": "false", "waiting": "false",
+                       "verdict": "<span class='verdict-rejected'>Wrong answer on test 2: expected &gt; 5, it&#8217;s 4</span>"}"#;
+        let v = Verdict::from_json(json).unwrap();
+        assert_eq!(
+            v.message(),
+            "Wrong answer on test 2: expected > 5, it\u{2019}s 4"
+        );
+    }
+
+    #[test]
+    fn test_checker_comment() {
+        let protocol = "Test: #1, time: 15 ms., memory: 0 KB, exit code: 0, \
+                        checker exit code: 0, verdict: OK\n\
+                        Input\n1 2\n\nOutput\n3\n\nAnswer\n3\n\n\
+                        Checker Log\nok 1 number(s): \"3\"\n\n\
+                        Test: #2, time: 15 ms., memory: 0 KB, exit code: 0, \
+                        checker exit code: 1, verdict: WRONG_ANSWER\n\
+                        Input\n2 3\n\nOutput\n4\n\nAnswer\n5\n\n\
+                        Checker Log\nwrong answer 1st numbers differ - \
+                        expected: '5', found: '4'\n\n";
+        assert_eq!(
+            parse_checker_comment(protocol).as_deref(),
+            Some("1st numbers differ - expected: '5', found: '4'")
+        );
+        assert_eq!(parse_checker_comment("Checker Log\n\n"), None);
+        assert_eq!(parse_checker_comment(""), None);
+
+        let json = r#"{"compilationError": "false", "waiting": "false",
+                       "verdict": "Wrong answer on test 2"}"#;
+        let mut v = Verdict::from_json(json).unwrap();
+        v.add_checker_comment(protocol);
+        assert_eq!(
+            v.message(),
+            "Wrong answer on test 2: 1st numbers differ - expected: '5', found: '4'"
+        );
+        assert_eq!(v.name(), "WRONG_ANSWER");
+        let mut v = Verdict::from_json(json).unwrap();
+        v.add_checker_comment("");
+        assert_eq!(v.message(), "Wrong answer on test 2");
+    }
+
+    #[test]
+    fn test_unavailable_message() {
+        let json = r#"{"error": "Codeforces is temporarily unavailable"}"#;
+        assert_eq!(
+            unavailable_message(json).as_deref(),
+            Some("Codeforces is temporarily unavailable")
+        );
+        assert_eq!(
+            unavailable_message("{}").as_deref(),
+            Some("unexpected response")
+        );
+        let json = r#"{"compilationError": "false", "verdict": "", "waiting": "true"}"#;
+        assert_eq!(unavailable_message(json), None);
+    }
+
+    #[test]
+    fn test_partial_skipped_and_hacked() {
+        let json = r#"{
+            "compilationError": "false",
+            "verdict": "<span class=\"verdict-rejected\">Partial result: 42 points</span>",
+            "waiting": "false"
+        }"#;
+        let v = Verdict::from_json(json).unwrap();
+        assert!(v.is_partial());
+        assert_eq!(v.name(), "PARTIAL");
+        assert_eq!(v.msg, "Partial result: 42 points");
+
+        let json = r#"{
+            "compilationError": "false",
+            "verdict": "<span class=\"verdict-rejected\">Skipped</span>",
+            "waiting": "false"
+        }"#;
+        let v = Verdict::from_json(json).unwrap();
+        assert_eq!(v.name(), "SKIPPED");
+        assert_eq!(v.exit_code(), 1);
+
+        let json = r#"{
+            "compilationError": "false",
+            "verdict": "<span class=\"verdict-hacked\">Hacked</span>",
+            "waiting": "false"
+        }"#;
+        let v = Verdict::from_json(json).unwrap();
+        assert!(matches!(v.code, VerdictCode::Hacked));
+        assert_eq!(v.name(), "CHALLENGED");
+    }
+
+    #[test]
+    fn test_time_and_memory() {
+        let json = r#"{
+            "compilationError": "false",
+            "verdict": "<span class=\"verdict-accepted\">Accepted</span>",
+            "waiting": "false",
+            "timeConsumedMillis": "140",
+            "memoryConsumedBytes": "12900000"
+        }"#;
+        let v = Verdict::from_json(json).unwrap();
+        let mut buf = termcolor::Buffer::no_color();
+        v.print(&mut buf, false).unwrap();
+        assert_eq!(buf.as_slice(), b"Accepted  140 ms  12.3 MB");
+    }
+
+    #[test]
+    fn test_idleness() {
+        let json = r#"{
+            "compilationError": "false",
+            "verdict": "<span class=\"verdict-rejected\">Idleness limit exceeded on test <span class=\"verdict-format-judged\">3</span></span>",
+            "waiting": "false"
+        }"#;
+        let v = Verdict::from_json(json).unwrap();
+        assert!(v.is_idleness());
+        assert_eq!(v.msg, "Idleness limit exceeded on test 3");
+        assert_eq!(v.name(), "IDLENESS_LIMIT_EXCEEDED");
+    }
+
+    #[test]
+    fn test_to_json() {
+        let json = r#"{
+            "compilationError": "false",
+            "verdict": "<span class=\"verdict-rejected\">Wrong answer on test 3</span>",
+            "waiting": "false",
+            "passedTestCount": "2",
+            "timeConsumedMillis": "15",
+            "memoryConsumedBytes": "102400"
+        }"#;
+        let v = Verdict::from_json(json).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&v.to_json("42", None)).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "id": "42",
+                "verdict": "WRONG_ANSWER",
+                "message": "Wrong answer on test 3",
+                "passed": 2,
+                "time_ms": 15,
+                "memory_kb": 100,
+                "protocol": null
+            })
+        );
+    }
+
+    #[test]
+    fn test_passed_test_count() {
+        let json = r#"{
+            "compilationError": "false",
+            "verdict": "<span class=\"verdict-rejected\">Wrong answer</span>",
+            "waiting": "false",
+            "passedTestCount": "6"
+        }"#;
+        let v = Verdict::from_json(json).unwrap();
+        assert_eq!(v.msg, "Wrong answer on test 7");
+        assert_eq!(v.name(), "WRONG_ANSWER");
+
+        let json = r#"{
+            "compilationError": "false",
+            "verdict": "<span class=\"verdict-accepted\">Accepted</span>",
+            "waiting": "false",
+            "passedTestCount": "42"
+        }"#;
+        let v = Verdict::from_json(json).unwrap();
+        let mut buf = termcolor::Buffer::no_color();
+        v.print(&mut buf, false).unwrap();
+        assert_eq!(buf.as_slice(), b"Accepted  42 tests");
+    }
+
+    #[test]
+    fn test_format_time_memory() {
+        assert_eq!(format_time(140), "140 ms");
+        assert_eq!(format_memory(0), "0 KB");
+        assert_eq!(format_memory(300 * 1024), "300 KB");
+        assert_eq!(format_memory(12_900_000), "12.3 MB");
+        assert_eq!(format_memory(256 * 1024 * 1024), "256.0 MB");
+    }
+
+    #[test]
+    fn test_running_without_test_number() {
+        let json = r#"{
+            "compilationError": "false",
+            "verdict": "<span class=\"verdict-format-judging\">Running</span>",
+            "waiting": "true"
+        }"#;
+        let v = Verdict::from_json(json).unwrap();
+        assert!(v.is_waiting());
+        assert_eq!(v.msg, "Running");
+        assert_eq!(v.exit_code(), 3);
+
+        let json = r#"{
+            "compilationError": "false",
+            "verdict": "<span class=\"verdict-format-judging\">Running on test 0</span>",
+            "waiting": "true",
+            "testNumber": "0"
+        }"#;
+        let v = Verdict::from_json(json).unwrap();
+        assert_eq!(v.msg, "Running");
+
+        let json = r#"{
+            "compilationError": "false",
+            "verdict": "<span class=\"verdict-format-judging\">Running on test 7</span>",
+            "waiting": "true",
+            "testNumber": "7"
+        }"#;
+        let v = Verdict::from_json(json).unwrap();
+        assert_eq!(v.msg, "Running on test 7");
+    }
+
+    #[test]
+    fn test_running_progress() {
+        // The test number is from testNumber, not the message.
+        let json = r#"{
+            "compilationError": "false",
+            "verdict": "<span class=\"verdict-format-judging\">Running on test <span class=\"verdict-format-judged\">11</span></span>",
+            "waiting": "true",
+            "testNumber": "12"
+        }"#;
+        let v = Verdict::from_json(json).unwrap();
+        assert_eq!(v.running_test, Some(12));
+        let mut buf = termcolor::Buffer::no_color();
+        v.print(&mut buf, false).unwrap();
+        assert_eq!(buf.as_slice(), b"Running on test 12...");
+
+        // Not running yet, no progress to show.
+        let json = r#"{"compilationError": "false", "verdict": "In queue", "waiting": "true"}"#;
+        let v = Verdict::from_json(json).unwrap();
+        assert_eq!(v.running_test, None);
+        let mut buf = termcolor::Buffer::no_color();
+        v.print(&mut buf, false).unwrap();
+        assert_eq!(buf.as_slice(), b"In queue");
+    }
+}
@@ -1,5 +1,8 @@
 mod app;
+mod clock;
 mod codeforces;
+mod unescape;
+use clock::{Clock, SystemClock};
 use codeforces::Codeforces;
 use codeforces::Verdict;
 use log::{debug, error, info, warn};
@@ -18,7 +21,39 @@ fn set_from_file(
     }
 }
 
-fn print_verdict(v: &Verdict, color: bool, id: &str) {
+/// Prompt for the two-factor authentication code on the terminal.
+fn read_totp_code() -> String {
+    use std::io::{IsTerminal, Write};
+    if !std::io::stdin().is_terminal() {
+        error!(
+            "two-factor authentication code required, \
+            use --totp or totp_secret in the config file"
+        );
+        exit(1);
+    }
+    eprint!("[cftool] two-factor authentication code: ");
+    let _ = std::io::stderr().flush();
+    let mut code = String::new();
+    std::io::stdin().read_line(&mut code).unwrap_or_else(|err| {
+        error!("failed reading the code: {}", err);
+        exit(1);
+    });
+    code.trim().to_owned()
+}
+
+/// Find the config file in the directory, `cftool.json` or `cftool.toml`
+/// if the former does not exist.
+fn find_config(dir: &std::path::Path) -> Option<std::path::PathBuf> {
+    ["cftool.json", "cftool.toml"]
+        .iter()
+        .map(|f| dir.join(f))
+        .find(|p| p.exists())
+}
+
+/// Print the verdict line.  If `in_place` is true, overwrite the current
+/// line and don't end it until the verdict is final, so the terminal isn't
+/// flooded while polling.
+fn print_verdict(v: &Verdict, color: bool, id: &str, in_place: bool, partial_ok: bool) {
     use std::io::Write;
     use termcolor::ColorChoice::Auto;
     use termcolor::{Buffer, BufferWriter};
@@ -29,69 +64,414 @@ fn print_verdict(v: &Verdict, color: bool, id: &str) {
         Buffer::no_color()
     };
 
+    if in_place {
+        write!(&mut buf, "\r").unwrap_or_else(|e| {
+            error!("can not buffer carriage return: {}", e);
+            exit(1);
+        });
+    }
+
     write!(&mut buf, "{} ", id).unwrap_or_else(|e| {
         error!("can not buffer submission ID: {}", e);
         exit(1);
     });
 
-    v.print(&mut buf).unwrap_or_else(|e| {
+    v.print(&mut buf, partial_ok).unwrap_or_else(|e| {
         error!("can not buffer verdict: {}", e);
         exit(1);
     });
 
+    // Erase the remaining of a longer line printed before.
+    let end = match (in_place, v.is_waiting()) {
+        (true, true) => "\x1b[K",
+        (true, false) => "\x1b[K\n",
+        (false, _) => "\n",
+    };
+    write!(&mut buf, "{}", end).unwrap_or_else(|e| {
+        error!("can not buffer line end: {}", e);
+        exit(1);
+    });
+
     w.print(&buf).unwrap_or_else(|e| {
         error!("can not output verdict: {}", e);
         exit(1);
     });
 }
 
-fn get_ce_info(cf: &mut Codeforces, id: &str) -> String {
+/// Where the poll loop gets the verdicts from, so it can be replaced in
+/// tests.
+trait VerdictSource {
+    fn get_verdict(&mut self, id: &str) -> Result<Verdict, codeforces::Error>;
+    fn judgement_protocol(&mut self, id: &str) -> Result<String, codeforces::Error>;
+    fn get_poll_interval(&self) -> std::time::Duration;
+    fn get_partial_ok(&self) -> bool;
+    fn retry_unavailable(&mut self, n: i64) -> bool;
+}
+
+impl VerdictSource for Codeforces {
+    fn get_verdict(&mut self, id: &str) -> Result<Verdict, codeforces::Error> {
+        Codeforces::get_verdict(self, id)
+    }
+
+    fn judgement_protocol(&mut self, id: &str) -> Result<String, codeforces::Error> {
+        Codeforces::judgement_protocol(self, id)
+    }
+
+    fn get_poll_interval(&self) -> std::time::Duration {
+        Codeforces::get_poll_interval(self)
+    }
+
+    fn get_partial_ok(&self) -> bool {
+        Codeforces::get_partial_ok(self)
+    }
+
+    fn retry_unavailable(&mut self, n: i64) -> bool {
+        Codeforces::retry_unavailable(self, n)
+    }
+}
+
+fn get_protocol<S: VerdictSource>(cf: &mut S, id: &str) -> String {
     cf.judgement_protocol(id).unwrap_or_else(|e| {
-        error!("can not get compilation error info: {}", e);
+        error!("can not get judgement protocol: {}", e);
         String::new()
     })
 }
 
-fn poll_or_query_verdict(cf: &mut Codeforces, poll: bool, no_color: bool) {
-    use std::time::{Duration, SystemTime};
-    let mut wait = true;
+fn get_last_submission(cf: &mut Codeforces) -> String {
     let id = cf.get_last_submission().unwrap_or_else(|e| {
         error!("cannot get ID of last submission: {}", e);
         exit(1);
     });
 
     info!("submission id = {}:", &id);
+    id
+}
 
-    while wait {
-        let next_try = SystemTime::now() + Duration::new(5, 0);
-        let v = cf.get_verdict(&id).unwrap_or_else(|e| {
-            error!("cannot get verdict: {}", e);
-            exit(1);
-        });
+/// Exit code if polling times out before the submission is judged.
+const POLL_TIMEOUT_EXIT_CODE: i32 = 4;
+
+/// Get the exit code for the verdict.  If we've polled but it's still
+/// waiting, the polling has timed out.
+fn verdict_exit_code(cf: &Codeforces, v: &Verdict, polled: bool) -> i32 {
+    if polled && v.is_waiting() {
+        POLL_TIMEOUT_EXIT_CODE
+    } else {
+        cf.exit_code(v)
+    }
+}
 
-        print_verdict(&v, !no_color, &id);
-        wait = v.is_waiting() && poll;
+/// Let the user know `what` is judged, with a desktop notification if
+/// `notify` is true and the terminal bell if `bell` is true.
+fn alert(what: &str, v: &Verdict, notify: bool, bell: bool) {
+    if v.is_waiting() {
+        return;
+    }
+    if bell {
+        // Not to stdout, so it never gets into the verdict output.
+        eprint!("\x07");
+    }
+    if !notify {
+        return;
+    }
+    let r = notify_rust::Notification::new()
+        .summary(&format!("cftool: {}", what))
+        .body(v.message())
+        .show();
+    if let Err(e) = r {
+        warn!("can not show notification: {}", e);
+    }
+}
 
-        if v.is_compilation_error() {
-            let s = get_ce_info(cf, &id);
-            println!("===================================");
-            print!("{}", s);
+/// POST the verdict to the webhook in the config files, once judged.
+fn post_webhook(cf: &Codeforces, id: &str, problem: Option<&str>, v: &Verdict) {
+    if v.is_waiting() {
+        return;
+    }
+    if let Err(e) = cf.post_webhook(id, problem, v) {
+        warn!("can not post the verdict to the webhook: {}", e);
+    }
+}
+
+/// How to print the verdicts.
+#[derive(Clone, Copy)]
+enum Output {
+    Text { color: bool },
+    Json,
+}
+
+/// Finish the waiting verdict line and tell the user polling has timed out.
+fn poll_timed_out(v: Verdict, in_place: bool) -> Verdict {
+    if in_place {
+        println!();
+    }
+    warn!("polling timed out, the submission is still being judged");
+    v
+}
+
+/// Get the verdict of submission `id` and print it, and if `poll` is true
+/// keep polling until it's judged or `timeout` has passed.
+fn poll_or_query_verdict<S: VerdictSource, C: Clock>(
+    cf: &mut S,
+    id: &str,
+    poll: bool,
+    out: Output,
+    timeout: Option<std::time::Duration>,
+    want_protocol: bool,
+    clock: &C,
+) -> Result<Verdict, codeforces::Error> {
+    use std::io::IsTerminal;
+    // Update the line in place only on a terminal, otherwise print each
+    // update on its own line.
+    let in_place = poll && std::io::stdout().is_terminal() && matches!(out, Output::Text { .. });
+    // The last verdict printed, while it's still waiting.
+    let mut last: Option<Verdict> = None;
+    let deadline = timeout.map(|t| clock.now() + t);
+    let timed_out = || deadline.is_some_and(|d| clock.now() >= d);
+    // Times Codeforces has been unavailable in a row.
+    let mut unavailable = 0;
+
+    loop {
+        let next_try = clock.now() + cf.get_poll_interval();
+        let mut v = match cf.get_verdict(id) {
+            Ok(v) => {
+                unavailable = 0;
+                v
+            }
+            Err(e) => {
+                if let codeforces::ErrorKind::Unavailable(_) = e.kind() {
+                    unavailable += 1;
+                    if poll && timed_out() {
+                        // As far as we know, it's still being judged.
+                        warn!("{}", e);
+                        let printed = last.is_some();
+                        let v = last.unwrap_or_else(|| Verdict::waiting(e.kind()));
+                        break Ok(poll_timed_out(v, in_place && printed));
+                    }
+                    if poll && cf.retry_unavailable(unavailable) {
+                        warn!("{}, retrying", e);
+                        clock.sleep_until(next_try);
+                        continue;
+                    }
+                }
+                return Err(e);
+            }
+        };
+
+        // The protocol is the compiler output for compilation errors, and
+        // checker comments etc. for other verdicts.  For a wrong answer we
+        // always need it for the checker comment.
+        let show_protocol = v.is_compilation_error() || (want_protocol && !v.is_waiting());
+        let protocol = if show_protocol || v.is_wrong_answer() {
+            Some(get_protocol(cf, id))
+        } else {
+            None
+        };
+        if let Some(s) = &protocol {
+            v.add_checker_comment(s);
+        }
+        let protocol = protocol.filter(|_| show_protocol);
+
+        match out {
+            Output::Json => println!("{}", v.to_json(id, protocol.as_deref())),
+            // Don't repeat the same "In queue" line again and again.
+            Output::Text { .. }
+                if v.is_waiting() && last.as_ref().map(Verdict::message) == Some(v.message()) => {}
+            Output::Text { color } => {
+                print_verdict(&v, color, id, in_place, cf.get_partial_ok());
+                if let Some(s) = protocol {
+                    // Keep stdout for the verdict, so scripts can parse it.
+                    eprintln!("===================================");
+                    eprint!("{}", s);
+                }
+            }
         }
+        let wait = v.is_waiting() && poll;
 
         if !wait {
-            break;
+            break Ok(v);
+        }
+        if timed_out() {
+            break Ok(poll_timed_out(v, in_place));
+        }
+        last = Some(v);
+        clock.sleep_until(next_try);
+    }
+}
+
+fn list_dialect_aliases() {
+    for (lang, table) in codeforces::DIALECT_ALIASES {
+        println!("{}:", lang);
+        for (_, aliases) in table.iter() {
+            println!("    {}", aliases.join(", "));
         }
-        if let Ok(d) = next_try.duration_since(SystemTime::now()) {
-            std::thread::sleep(d);
+    }
+}
+
+/// Print one dialect per line: the programTypeId, the names for --dialect,
+/// and the names for the config files.
+fn list_languages() {
+    for (dialect, names) in codeforces::DIALECT_NAMES {
+        let aliases: Vec<_> = codeforces::DIALECT_ALIASES
+            .iter()
+            .flat_map(|(_, table)| table.iter())
+            .filter(|(d, _)| d == dialect)
+            .flat_map(|(_, aliases)| aliases.iter().copied())
+            .collect();
+        let mut line = format!("{:>3}  {}", dialect.to_id(), names.join(", "));
+        if !aliases.is_empty() {
+            line = line + "  (config: " + &aliases.join(", ") + ")";
+        }
+        println!("{}", line);
+    }
+}
+
+const CONFIG_TEMPLATE: &str = include_str!("../example/cftool.toml");
+
+/// Write the template config file into the directory.  Return the path of
+/// the written file.
+fn init_config(dir: &std::path::Path, force: bool) -> std::io::Result<std::path::PathBuf> {
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join("cftool.toml");
+    let mut opts = std::fs::OpenOptions::new();
+    if force {
+        opts.create(true).truncate(true);
+    } else {
+        opts.create_new(true);
+    }
+    opts.write(true).open(&path).and_then(|mut f| {
+        use std::io::Write;
+        f.write_all(CONFIG_TEMPLATE.as_bytes())
+    })?;
+    if dir.join("cftool.json").exists() {
+        warn!(
+            "{} exists and it's used instead of cftool.toml",
+            dir.join("cftool.json").display()
+        );
+    }
+    Ok(path)
+}
+
+/// Split a problem ID like `1234/C` into the contest ID and the problem
+/// index.  If the part before '/' is not a number, the whole string is
+/// treated as a problem index.
+fn split_problem(s: &str) -> (Option<&str>, &str) {
+    if let Some((contest, index)) = s.split_once('/') {
+        if !contest.is_empty() && contest.bytes().all(|c| c.is_ascii_digit()) {
+            return (Some(contest), index);
+        }
+    }
+    (None, s)
+}
+
+/// Split a token like `1234C` into the contest ID and the problem index.
+fn split_contest_problem(s: &str) -> Option<(&str, &str)> {
+    let re = regex::Regex::new(r"^([0-9]+)([A-Za-z][0-9]*)$").unwrap();
+    let caps = re.captures(s)?;
+    Some((caps.get(1)?.as_str(), caps.get(2)?.as_str()))
+}
+
+/// Show what is going to be submitted and ask for confirmation.  Returns
+/// true without asking if stdin is not a terminal.
+fn confirm_submit(
+    cf: &Codeforces,
+    problem: &str,
+    source: &str,
+    dialect: Option<&str>,
+    forced: bool,
+) -> bool {
+    use std::io::{IsTerminal, Write};
+    if !std::io::stdin().is_terminal() {
+        info!("stdin is not a terminal, submitting without confirmation");
+        return true;
+    }
+
+    let dialect = match cf.resolve_dialect(source, dialect) {
+        Ok((Some(d), id)) => format!("{} (programTypeId {})", d.name(), id),
+        Ok((None, id)) => format!("programTypeId {}", id),
+        Err(e) => {
+            error!("{}", e);
+            exit(1);
         }
+    };
+    let source = if source == "-" { "(stdin)" } else { source };
+
+    eprintln!("problem: {}", problem);
+    if forced {
+        eprintln!("         (not checked because of --force)");
+    }
+    eprintln!("dialect: {}", dialect);
+    eprintln!("source:  {}", source);
+    eprint!("Submit? [y/N] ");
+    let _ = std::io::stderr().flush();
+
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer).is_err() {
+        return false;
     }
+    matches!(answer.trim(), "y" | "Y" | "yes" | "Yes" | "YES")
 }
 
+fn print_timings(cf: &Codeforces) {
+    eprintln!(
+        "{:<10} {:>5} {:>10} {:>10} {:>10}",
+        "request", "count", "total", "avg", "max"
+    );
+    for (category, timings) in cf.get_timings() {
+        let total: std::time::Duration = timings.iter().sum();
+        let max = timings.iter().max().copied().unwrap_or_default();
+        let avg = total / timings.len().max(1) as u32;
+        eprintln!(
+            "{:<10} {:>5} {:>7} ms {:>7} ms {:>7} ms",
+            category,
+            timings.len(),
+            total.as_millis(),
+            avg.as_millis(),
+            max.as_millis()
+        );
+    }
+}
+
+/// Print the timing summary if --benchmark is used, then exit.
+fn finish(cf: &Codeforces, benchmark: bool, code: i32) -> ! {
+    if benchmark {
+        print_timings(cf);
+    }
+    exit(code);
+}
+
+/// Expand a glob pattern to the most recently modified matching file.
+fn newest_match(pattern: &str) -> Result<String, String> {
+    let paths = glob::glob(pattern).map_err(|e| format!("bad glob pattern {}: {}", pattern, e))?;
+    paths
+        .filter_map(|p| p.ok())
+        .filter_map(|p| {
+            let mtime = std::fs::metadata(&p).and_then(|m| m.modified()).ok()?;
+            Some((mtime, p))
+        })
+        .max_by_key(|(mtime, _)| *mtime)
+        .map(|(_, p)| p.to_string_lossy().into_owned())
+        .ok_or_else(|| format!("no file matches {}", pattern))
+}
+
+/// The options choosing what to do, for the error messages.
+const ACTION_FLAGS: &str = "--dry-run, --query, --problem, --resubmit, \
+                            --samples, --list, --get-source, --countdown, \
+                            --register, --clarifications, and --ask";
+
 enum Action {
     None,
     Dry,
     Query,
+    Samples(String),
+    List(usize),
+    GetSource(String),
+    Countdown,
+    Register,
+    Clarifications,
+    Ask(String),
     Submit(String),
+    // Submit to the problem of the last submission, known after login.
+    Resubmit,
     Err(String),
 }
 
@@ -114,7 +494,7 @@ impl Action {
 
 fn main() {
     use app::Parser;
-    let args = app::App::parse();
+    let mut args = app::App::parse();
     let v = args.verbose.checked_add(1).unwrap_or(u8::MAX);
     let modules = &[module_path!(), "reqwest"];
     stderrlog::new()
@@ -125,47 +505,152 @@ fn main() {
 
     info!("this is XDU-ICPC cftool, {}", app::VERSION);
 
-    let mut action = Action::None;
+    if args.list_dialect_aliases {
+        list_dialect_aliases();
+        exit(0);
+    }
 
-    if let Some(problem) = args.problem {
-        action = Action::submit(problem, args.force);
+    if args.list_languages {
+        list_languages();
+        exit(0);
     }
 
-    let conflict_msg = "can only use one of --dry-run, --query, \
-                        and --problem";
-    if args.dry_run {
-        if let Action::None = action {
-            action = Action::Dry;
+    if args.init || args.init_user {
+        let dir = if args.init {
+            std::path::PathBuf::from(".")
         } else {
-            error!("{}", conflict_msg);
+            match directories::ProjectDirs::from("cn.edu.xidian.acm", "XDU-ICPC", "cftool") {
+                Some(dir) => dir.config_dir().to_owned(),
+                None => {
+                    error!("can not get the path of user config file on the system");
+                    exit(1);
+                }
+            }
+        };
+        match init_config(&dir, args.force) {
+            Ok(path) => println!("{}", path.display()),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                error!(
+                    "{} already exists, use --force to overwrite it",
+                    dir.join("cftool.toml").display()
+                );
+                exit(1);
+            }
+            Err(e) => {
+                error!("can not write the config file: {}", e);
+                exit(1);
+            }
+        }
+        exit(0);
+    }
+
+    let mut action = Action::None;
+    let mut problem_contest = None;
+
+    if let Some(problem) = args.problem.as_deref() {
+        let (contest, index) = split_problem(problem);
+        problem_contest = contest.map(|c| format!("contest/{}", c));
+        action = Action::submit(index, args.force);
+    }
+
+    if let Some(problem) = args.samples.as_deref() {
+        let (contest, index) = split_problem(problem);
+        problem_contest = contest.map(|c| format!("contest/{}", c));
+        action = match Action::submit(index, args.force) {
+            Action::Submit(p) => Action::Samples(p),
+            a => a,
+        };
+    }
+
+    let mut url_contest = None;
+    if let Some(u) = args.url.as_deref() {
+        match codeforces::parse_submission_url(u) {
+            Ok((contest, id)) => {
+                url_contest = Some(contest);
+                args.submission_id = Some(id);
+            }
+            Err(e) => {
+                error!("{}", e);
+                exit(1);
+            }
+        }
+    }
+
+    let mut target_contest = None;
+    if let Some(target) = args.target.as_deref() {
+        let (contest, problem) = split_contest_problem(target).unwrap_or_else(|| {
+            error!("{} does not look like a contest and problem ID", target);
             exit(1);
+        });
+        target_contest = Some((format!("contest/{}", contest), target.to_owned()));
+        if action.is_none() {
+            action = Action::submit(problem, args.force);
         }
     }
 
-    if args.query {
-        if let Action::None = action {
-            action = Action::Query;
-        } else {
-            error!("{}", conflict_msg);
+    if args.resubmit {
+        action = Action::Resubmit;
+    }
+
+    if let Some(pattern) = args.source_glob.as_deref() {
+        match newest_match(pattern) {
+            Ok(source) => {
+                info!("using {} matching {}", source, pattern);
+                args.source = Some(source);
+            }
+            Err(e) => {
+                error!("{}", e);
+                exit(1);
+            }
+        }
+    }
+
+    if args.source.is_none() {
+        args.source = args.target_source.take();
+    }
+
+    // The flags choosing an action, at most one of them can be used, and
+    // not with --problem, --samples, or --resubmit either.
+    let chosen = [
+        args.dry_run.then_some(Action::Dry),
+        args.query.then_some(Action::Query),
+        args.list.map(Action::List),
+        args.get_source.take().map(Action::GetSource),
+        args.countdown.then_some(Action::Countdown),
+        args.register.then_some(Action::Register),
+        args.clarifications.then_some(Action::Clarifications),
+        args.ask.take().map(Action::Ask),
+    ];
+    for a in chosen.into_iter().flatten() {
+        if !action.is_none() {
+            error!("can only use one of {}", ACTION_FLAGS);
             exit(1);
         }
+        action = a;
     }
 
-    let need_poll = args.poll;
+    // --no-poll always wins over --poll.
+    let need_poll = args.poll && !args.no_poll;
 
     if let Some(source) = args.source.as_ref() {
         match &action {
-            Action::Dry | Action::Query => {
+            Action::Submit(_) | Action::Resubmit | Action::Err(_) => (),
+            Action::None if source == "-" => {
                 error!(
-                    "specifying source code file does not make sense \
-                    without submitting it"
+                    "can't guess problem ID when reading the source from \
+                    stdin, please specify it explicitly"
                 );
                 exit(1);
             }
-            Action::Submit(_) => (),
             Action::None => {
                 let path = std::path::Path::new(&source);
                 if let Some(s) = path.file_stem().and_then(|x| x.to_str()) {
+                    // With --source-glob the files are iterations like
+                    // A_v2.cpp, the problem ID is before the underscore.
+                    let s = match args.source_glob {
+                        Some(_) => s.split('_').next().unwrap_or(s),
+                        None => s,
+                    };
                     action = Action::submit(s, args.force);
                 } else {
                     error!(
@@ -177,48 +662,99 @@ fn main() {
                     info!("guessed problem ID to be {}", problem);
                 }
             }
-            Action::Err(_) => (),
+            _ => {
+                error!(
+                    "specifying source code file does not make sense \
+                    without submitting it"
+                );
+                exit(1);
+            }
         }
     }
 
-    if need_poll && action.is_none() {
+    if (need_poll || args.submission_id.is_some()) && action.is_none() {
         action = Action::Query;
     }
 
+    if args.submission_id.is_some() && !matches!(action, Action::Query) {
+        error!("--submission-id only makes sense with --query or --poll");
+        exit(1);
+    }
+
+    if args.command.is_some() && !action.is_none() {
+        error!("subcommands can't be used with {}", ACTION_FLAGS);
+        exit(1);
+    }
+
+    // Options only doing something locally don't need an action.
+    let local_only = args.print_cookie_path
+        || args.list_accounts
+        || args.show_config
+        || args.logout
+        || args.command.is_some();
+
     match &action {
+        Action::None if local_only => (),
         Action::None => {
-            error!("must use one of --dry-run, --query, and --problem");
+            error!("must use one of {}", ACTION_FLAGS);
             exit(1);
         }
-        Action::Submit(_) => {
+        Action::Submit(_) | Action::Resubmit => {
             if args.source.is_none() {
                 error!("attempt to submit, but no source code specified");
                 exit(1);
             }
+            if args.source.as_deref() == Some("-") && args.dialect.is_none() {
+                error!("--dialect is required when reading the source from stdin");
+                exit(1);
+            }
         }
         Action::Err(s) => {
             error!("{}", s);
             exit(1);
         }
-        Action::Dry | Action::Query => (),
+        _ => (),
     };
 
-    let no_color = args.no_color;
+    let timeout = args.poll_timeout.map(std::time::Duration::from_secs);
+
+    let out = match args.format.as_str() {
+        "json" => Output::Json,
+        _ => Output::Text {
+            color: !args.no_color,
+        },
+    };
 
     let mut builder = Codeforces::builder();
     let mut cookie_dir = None;
 
+    if let Some(profile) = args.profile.as_deref() {
+        builder = builder.profile(profile);
+    }
+
     let project_dirs = directories::ProjectDirs::from("cn.edu.xidian.acm", "XDU-ICPC", "cftool");
     match &project_dirs {
         Some(dir) => {
             // Override configuration from user config file.
-            let config_file = dir.config_dir().join("cftool.json");
-            if config_file.exists() {
-                builder = set_from_file(builder, &config_file);
-            } else {
-                info!("user config file {} does not exist", config_file.display());
+            match find_config(dir.config_dir()) {
+                Some(config_file) => builder = set_from_file(builder, &config_file),
+                None => info!(
+                    "user config file cftool.json or cftool.toml does not \
+                     exist in {}",
+                    dir.config_dir().display()
+                ),
             }
             cookie_dir = Some(dir.cache_dir().join("cookie"));
+            let unfold_dir = dir.cache_dir().join("unfold");
+            match std::fs::create_dir_all(&unfold_dir) {
+                Ok(()) => builder = builder.unfold_cache_dir(unfold_dir),
+                Err(e) => info!(
+                    "can not create cache dir {}, unfolded code won't be \
+                     cached: {}",
+                    unfold_dir.display(),
+                    e
+                ),
+            }
         }
         None => {
             warn!(
@@ -247,41 +783,206 @@ fn main() {
 
     // Override configuration from the config file in working directory.
     debug!(
-        "trying to read config file cftool.json in the working \
-         directory"
+        "trying to read config file cftool.json or cftool.toml in the \
+         working directory"
     );
-    let config_file = std::path::Path::new("cftool.json");
-    if config_file.exists() {
-        builder = set_from_file(builder, config_file);
-    } else {
-        debug!("cftool.json does not exist")
+    match find_config(std::path::Path::new(".")) {
+        Some(config_file) => builder = set_from_file(builder, &config_file),
+        None => debug!("cftool.json and cftool.toml do not exist"),
     }
 
-    if let Some(custom_config) = args.config {
-        let path = std::path::Path::new(&custom_config);
+    // Apply custom config files in the order they are specified.
+    for custom_config in &args.config {
+        let path = std::path::Path::new(custom_config);
         builder = set_from_file(builder, path);
     }
 
     if let Some(path) = args.cookie {
-        builder = builder.cookie_file(std::path::PathBuf::from(path));
+        builder = builder
+            .cookie_file(std::path::PathBuf::from(path))
+            .origin("cookie_file", "--cookie");
     }
 
     if let Some(server) = args.server {
-        builder = builder.server_url(&server);
+        builder = builder.server_url(&server).origin("server_url", "--server");
+    }
+
+    if let Some(n) = args.mirror {
+        // The mirrors may not support HTTP/2.
+        builder = builder
+            .server_url(&format!("https://m{}.codeforces.com", n))
+            .origin("server_url", "--mirror")
+            .http1(true);
     }
 
     if let Some(identy) = args.identy {
-        builder = builder.identy(identy);
+        builder = builder.identy(identy).origin("identy", "--identy");
+    }
+
+    if let Some((contest, target)) = target_contest {
+        builder = builder
+            .contest_path(contest)
+            .origin("contest_path", format!("argument \"{}\"", target));
     }
 
     if let Some(contest) = args.contest {
-        builder = builder.contest_path(contest);
+        builder = builder
+            .contest_path(contest)
+            .origin("contest_path", "--contest");
+    }
+
+    if let Some(id) = args.gym {
+        if id.is_empty() || !id.bytes().all(|c| c.is_ascii_digit()) {
+            error!("{} does not look like a gym contest ID", id);
+            exit(1);
+        }
+        builder = builder
+            .contest_path(format!("gym/{}", id))
+            .origin("contest_path", "--gym");
+    }
+
+    // The contest in the problem ID is more specific than --contest.
+    if let Some(contest) = problem_contest {
+        info!("using contest path {} from the problem ID", contest);
+        builder = builder
+            .contest_path(contest)
+            .origin("contest_path", "--problem");
+    }
+
+    if let Some(contest) = url_contest {
+        info!("using contest path {} from the submission URL", contest);
+        builder = builder
+            .contest_path(contest)
+            .origin("contest_path", "--url");
+    }
+
+    if args.partial_ok {
+        builder = builder.partial_ok(true);
+    }
+
+    if args.strict_dialect {
+        builder = builder.strict_dialect(true);
+    }
+
+    if let Some(n) = args.timeout_retries {
+        builder = builder
+            .timeout_retries(n)
+            .origin("timeout_retries", "--timeout-retries");
+    }
+
+    if let Some(n) = args.retry_budget {
+        builder = builder.retry_budget(n);
+    }
+
+    if let Some(n) = args.poll_interval {
+        builder = builder.poll_interval(n);
+    }
+
+    if let Some(n) = args.timeout {
+        builder = builder.timeout(n);
+    }
+
+    if args.http1 {
+        builder = builder.http1(true);
+    }
+
+    if let Some(proxy) = args.proxy.as_deref() {
+        builder = builder.proxy(proxy).origin("proxy", "--proxy");
+    }
+
+    if args.bump {
+        builder = builder.bump(true);
+    }
+
+    if args.no_rustfmt {
+        builder = builder.no_rustfmt(true);
+    }
+
+    if args.strip_comments {
+        builder = builder.strip_comments(true);
+    }
+
+    for cond in &args.skip_cfg {
+        builder = builder.skip_cfg(cond);
+    }
+
+    if let Some(n) = args.tab_size {
+        builder = builder.tab_size(n);
+    }
+
+    if let Some(app::Command::Unfold { file, output }) = &args.command {
+        let code = builder.unfold_rust(file).unwrap_or_else(|e| {
+            match e {
+                // It already says which file can't be read.
+                codeforces::UnfoldError::IO(..) => error!("cannot unfold: {}", e),
+                e => error!("cannot unfold {}: {}", file, e),
+            }
+            exit(1);
+        });
+        match output {
+            Some(path) => std::fs::write(path, code).unwrap_or_else(|e| {
+                error!("can not write {}: {}", path, e);
+                exit(1);
+            }),
+            None => print!("{}", code),
+        }
+        exit(0);
     }
 
-    if builder.have_server_url_override() {
+    if args.print_cookie_path {
+        match builder.cookie_path() {
+            Ok(Some(p)) => println!("{}", p.display()),
+            Ok(None) => info!("cookie won't be saved"),
+            Err(e) => {
+                error!("can not get cookie path: {}", e);
+                exit(1);
+            }
+        }
+        exit(0);
+    }
+
+    if args.logout {
+        match builder.cookie_path() {
+            Ok(Some(p)) => match std::fs::remove_file(&p) {
+                Ok(()) => println!("{}", p.display()),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    info!("{} does not exist, already logged out", p.display())
+                }
+                Err(e) => {
+                    error!("can not remove {}: {}", p.display(), e);
+                    exit(1);
+                }
+            },
+            Ok(None) => info!("cookie is not saved, nothing to remove"),
+            Err(e) => {
+                error!("can not get cookie path: {}", e);
+                exit(1);
+            }
+        }
+        exit(0);
+    }
+
+    if args.list_accounts {
+        match builder.list_accounts() {
+            Ok(accounts) => {
+                for (identy, alive) in accounts {
+                    let state = if alive { "logged in" } else { "expired" };
+                    println!("{}\t{}", identy, state);
+                }
+            }
+            Err(e) => {
+                error!("can not list accounts: {}", e);
+                exit(1);
+            }
+        }
+        exit(0);
+    }
+
+    if builder.have_server_url_override() && !builder.get_http1() {
         warn!(
             "overriding server_url requires that the server supports \
-            HTTP/2.0, and is not recommended for normal use!"
+            HTTP/2.0 unless --http1 is used, and is not recommended for \
+            normal use!"
         );
     }
 
@@ -290,6 +991,15 @@ fn main() {
         exit(1);
     });
 
+    if args.show_config {
+        for (key, value, origin) in cf.effective_config() {
+            println!("{:<16}{:<48}({})", key, value, origin);
+        }
+        exit(0);
+    }
+
+    cf.set_prompt(Box::new(|prompt| rpassword::prompt_password(prompt).ok()));
+
     let dialect = args.dialect.as_deref();
 
     let logon = cf.probe_login_status().unwrap_or_else(|e| {
@@ -308,10 +1018,27 @@ fn main() {
             exit(1);
         });
 
-        cf.login(&passwd).unwrap_or_else(|err| {
-            error!("failed to login: {}", err);
-            exit(1);
-        });
+        match cf.login(&passwd) {
+            Ok(()) => (),
+            Err(e) if matches!(e.kind(), codeforces::ErrorKind::TotpRequired) => {
+                let code = match (args.totp.take(), cf.totp_code()) {
+                    (Some(code), _) => code,
+                    (None, Some(code)) => code.unwrap_or_else(|err| {
+                        error!("{}", err);
+                        exit(1);
+                    }),
+                    (None, None) => read_totp_code(),
+                };
+                cf.login_totp(&code).unwrap_or_else(|err| {
+                    error!("failed to login: {}", err);
+                    exit(1);
+                });
+            }
+            Err(e) => {
+                error!("failed to login: {}", e);
+                exit(1);
+            }
+        }
 
         // Retry to GET the submit page.
         let logon = cf.probe_login_status().unwrap_or_else(|e| {
@@ -340,21 +1067,391 @@ fn main() {
 
     let problem = match action {
         Action::Submit(p) => p,
-        Action::Dry => exit(0),
+        Action::Resubmit => {
+            let p = cf.get_last_problem().unwrap_or_else(|e| {
+                error!("cannot get problem of last submission: {}", e);
+                exit(1);
+            });
+            info!("resubmitting to problem {}", p);
+            p
+        }
+        Action::Dry => finish(&cf, args.benchmark, 0),
+        Action::Samples(p) => {
+            let samples = cf.get_samples(&p).unwrap_or_else(|e| {
+                error!("cannot get sample tests of problem {}: {}", p, e);
+                exit(1);
+            });
+            for (i, s) in samples.iter().enumerate() {
+                println!("=== input #{} ===", i + 1);
+                print!("{}", s.input);
+                println!("=== output #{} ===", i + 1);
+                print!("{}", s.output);
+            }
+            finish(&cf, args.benchmark, 0);
+        }
+        Action::Countdown => {
+            match cf.get_countdown() {
+                Ok(Some(d)) => {
+                    let s = d.as_secs();
+                    println!(
+                        "the contest starts in {:02}:{:02}:{:02}",
+                        s / 3600,
+                        s / 60 % 60,
+                        s % 60
+                    );
+                }
+                Ok(None) => println!("the contest has started"),
+                Err(e) => {
+                    error!("cannot get countdown: {}", e);
+                    exit(1);
+                }
+            }
+            finish(&cf, args.benchmark, 0);
+        }
+        Action::Register => {
+            match cf.register(args.team.as_deref()) {
+                Ok(true) => println!("registered for {}", cf.get_contest_path()),
+                Ok(false) => println!("already registered for {}", cf.get_contest_path()),
+                Err(e) => {
+                    error!("cannot register: {}", e);
+                    exit(1);
+                }
+            }
+            finish(&cf, args.benchmark, 0);
+        }
+        Action::Clarifications => {
+            let v = cf.get_clarifications().unwrap_or_else(|e| {
+                error!("cannot get clarifications: {}", e);
+                exit(1);
+            });
+            if v.is_empty() {
+                println!("no clarifications");
+            }
+            for c in v {
+                let problem = if c.problem.is_empty() {
+                    "general".to_owned()
+                } else {
+                    format!("problem {}", c.problem)
+                };
+                println!("[{}] Q: {}", problem, c.question);
+                println!("A: {}", c.answer);
+                println!();
+            }
+            finish(&cf, args.benchmark, 0);
+        }
+        Action::Ask(text) => {
+            cf.ask_question(&text).unwrap_or_else(|e| {
+                error!("cannot ask the question: {}", e);
+                exit(1);
+            });
+            println!("question sent, use `cftool --clarifications` to see the answer");
+            finish(&cf, args.benchmark, 0);
+        }
+        Action::GetSource(id) => {
+            let src = cf.get_source(&id).unwrap_or_else(|e| {
+                error!("{}", e);
+                exit(1);
+            });
+            match args.output.as_deref() {
+                Some(path) => std::fs::write(path, src).unwrap_or_else(|e| {
+                    error!("can not write {}: {}", path, e);
+                    exit(1);
+                }),
+                None => print!("{}", src),
+            }
+            finish(&cf, args.benchmark, 0);
+        }
+        Action::List(n) => {
+            let rows = cf.get_submissions(n).unwrap_or_else(|e| {
+                error!("cannot list submissions: {}", e);
+                exit(1);
+            });
+            if rows.is_empty() {
+                info!("no submissions found for this contest");
+            }
+            for r in rows {
+                println!(
+                    "{:<12}{:<8}{:<40}{:>10}{:>12}",
+                    r.id, r.problem, r.verdict, r.time, r.memory
+                );
+            }
+            finish(&cf, args.benchmark, 0);
+        }
         Action::Query => {
-            poll_or_query_verdict(&mut cf, need_poll, no_color);
-            exit(0);
+            let id = match args.submission_id {
+                Some(id) => id,
+                None => get_last_submission(&mut cf),
+            };
+            let v = poll_or_query_verdict(
+                &mut cf,
+                &id,
+                need_poll,
+                out,
+                timeout,
+                args.protocol,
+                &SystemClock,
+            )
+            .unwrap_or_else(|e| {
+                error!("cannot get verdict: {}", e);
+                exit(1);
+            });
+            alert(&format!("submission {}", id), &v, args.notify, args.bell);
+            post_webhook(&cf, &id, None, &v);
+            finish(&cf, args.benchmark, verdict_exit_code(&cf, &v, need_poll));
         }
         Action::None | Action::Err(_) => unreachable!(),
     };
 
     let source = args.source.unwrap();
-    cf.submit(&problem, &source, dialect).unwrap_or_else(|err| {
-        error!("submit failed: {}", err);
-        exit(1);
-    });
+    let attempt_tag = args.attempt_tag.as_deref();
+    if args.interactive && !args.yes && !confirm_submit(&cf, &problem, &source, dialect, args.force)
+    {
+        warn!("submission cancelled");
+        finish(&cf, args.benchmark, 1);
+    }
+    cf.submit(&problem, &source, dialect, attempt_tag)
+        .unwrap_or_else(|err| {
+            error!("submit failed: {}", err);
+            exit(1);
+        });
+
+    let id = match cf.get_last_submission() {
+        Ok(id) => id,
+        Err(e) => {
+            // The code is submitted anyway, so it's not a failure.
+            warn!("submitted, but cannot get ID of the submission: {}", e);
+            finish(&cf, args.benchmark, 0);
+        }
+    };
+    info!("submission id = {}:", &id);
+    match cf.submission_url(&id) {
+        Ok(u) => {
+            // Keep stdout for JSON only.
+            if matches!(out, Output::Json) {
+                info!("submission: {}", u);
+            } else {
+                println!("submission: {}", u);
+            }
+            // It fails on systems without a browser, like a headless
+            // server.  That's fine.
+            if args.open {
+                if let Err(e) = webbrowser::open(u.as_str()) {
+                    info!("can not open {} in the browser: {}", u, e);
+                }
+            }
+        }
+        Err(e) => warn!("{}", e),
+    }
+    // poll_after_submit only affects submitting, so it's not considered
+    // when deciding the action above.
+    if need_poll || (cf.get_poll_after_submit() && !args.no_poll) {
+        let v = poll_or_query_verdict(
+            &mut cf,
+            &id,
+            true,
+            out,
+            timeout,
+            args.protocol,
+            &SystemClock,
+        )
+        .unwrap_or_else(|e| {
+            error!("cannot get verdict: {}", e);
+            exit(1);
+        });
+        alert(&format!("problem {}", problem), &v, args.notify, args.bell);
+        post_webhook(&cf, &id, Some(&problem), &v);
+        finish(&cf, args.benchmark, verdict_exit_code(&cf, &v, true));
+    }
+
+    // Not to stdout, which is for the verdict or the JSON.
+    eprintln!(
+        "submitted, use `cftool -o {} -q --submission-id {}` to check the verdict",
+        cf.get_contest_path(),
+        id
+    );
+    finish(&cf, args.benchmark, 0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clock::FakeClock;
+    use std::collections::VecDeque;
+    use std::time::Duration;
+
+    /// Give the verdicts in the queue one by one, and count the polls.
+    struct FakeSource {
+        verdicts: VecDeque<Result<Verdict, codeforces::Error>>,
+        polls: usize,
+        retry_limit: i64,
+    }
+
+    impl FakeSource {
+        fn new<I: IntoIterator<Item = Result<Verdict, codeforces::Error>>>(v: I) -> Self {
+            Self {
+                verdicts: v.into_iter().collect(),
+                polls: 0,
+                retry_limit: 3,
+            }
+        }
+    }
+
+    impl VerdictSource for FakeSource {
+        fn get_verdict(&mut self, _: &str) -> Result<Verdict, codeforces::Error> {
+            self.polls += 1;
+            self.verdicts.pop_front().expect("polled too many times")
+        }
+
+        fn judgement_protocol(&mut self, _: &str) -> Result<String, codeforces::Error> {
+            Ok(String::new())
+        }
+
+        fn get_poll_interval(&self) -> Duration {
+            Duration::new(2, 0)
+        }
+
+        fn get_partial_ok(&self) -> bool {
+            false
+        }
+
+        fn retry_unavailable(&mut self, n: i64) -> bool {
+            n <= self.retry_limit
+        }
+    }
+
+    fn running(test: u32) -> Result<Verdict, codeforces::Error> {
+        let json = format!(
+            r#"{{"compilationError": "false", "waiting": "true", "testNumber": "{0}",
+                "verdict": "Running on test {0}"}}"#,
+            test
+        );
+        Ok(Verdict::from_json(&json).unwrap())
+    }
+
+    fn unavailable() -> Result<Verdict, codeforces::Error> {
+        Err(codeforces::ErrorKind::Unavailable("overloaded".to_owned()).into())
+    }
+
+    fn accepted() -> Result<Verdict, codeforces::Error> {
+        let json = r#"{"compilationError": "false", "waiting": "false",
+            "verdict": "<span class=\"verdict-accepted\">Accepted</span>"}"#;
+        Ok(Verdict::from_json(json).unwrap())
+    }
+
+    // The JSON lines go through println!, so the test harness captures them.
+    const OUT: Output = Output::Json;
+
+    #[test]
+    fn test_poll_until_judged() {
+        let clock = FakeClock::new();
+        let start = clock.now();
+        let mut src = FakeSource::new([running(1), running(2), accepted()]);
+        let v = poll_or_query_verdict(&mut src, "42", true, OUT, None, false, &clock).unwrap();
+        assert_eq!(v.message(), "Accepted");
+        assert_eq!(src.polls, 3);
+        assert_eq!(clock.now(), start + Duration::new(4, 0));
+    }
+
+    #[test]
+    fn test_query_once() {
+        let clock = FakeClock::new();
+        let start = clock.now();
+        let mut src = FakeSource::new([running(1)]);
+        let v = poll_or_query_verdict(&mut src, "42", false, OUT, None, false, &clock).unwrap();
+        assert!(v.is_waiting());
+        assert_eq!(clock.now(), start);
+    }
+
+    #[test]
+    fn test_unavailable_retries() {
+        let clock = FakeClock::new();
+        let v = [unavailable(), unavailable(), running(1), unavailable()];
+        let mut src = FakeSource::new(v.into_iter().chain([unavailable(), accepted()]));
+        let v = poll_or_query_verdict(&mut src, "42", true, OUT, None, false, &clock).unwrap();
+        assert_eq!(v.message(), "Accepted");
+
+        // Give up after retry_limit retries in a row.
+        let mut src = FakeSource::new((0..5).map(|_| unavailable()));
+        let e = poll_or_query_verdict(&mut src, "42", true, OUT, None, false, &clock);
+        assert!(e.is_err());
+        assert_eq!(src.polls, 4);
+    }
+
+    #[test]
+    fn test_verdict_exit_code_partial_ok() {
+        let json = r#"{"compilationError": "false", "waiting": "false",
+            "verdict": "<span class=\"verdict-rejected\">Partial result: 42 points</span>"}"#;
+        let partial = Verdict::from_json(json).unwrap();
+        let builder = |partial_ok| {
+            Codeforces::builder()
+                .identy("tourist")
+                .contest_path("contest/1234")
+                .no_cookie(true)
+                .partial_ok(partial_ok)
+        };
+        let cf = builder(false).build().unwrap();
+        assert_eq!(verdict_exit_code(&cf, &partial, false), 1);
+        let cf = builder(true).build().unwrap();
+        assert_eq!(verdict_exit_code(&cf, &partial, false), 0);
+        assert_eq!(verdict_exit_code(&cf, &accepted().unwrap(), false), 0);
+        // exit_codes in the config files still wins.
+        let cf = builder(true).exit_code("PARTIAL", 5).build().unwrap();
+        assert_eq!(verdict_exit_code(&cf, &partial, false), 5);
+    }
+
+    fn test_cf() -> Codeforces {
+        Codeforces::builder()
+            .identy("tourist")
+            .contest_path("contest/1234")
+            .no_cookie(true)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_poll_timeout() {
+        let clock = FakeClock::new();
+        let start = clock.now();
+        let timeout = Some(Duration::new(5, 0));
+        let mut src = FakeSource::new((1..=4).map(running));
+        let v = poll_or_query_verdict(&mut src, "42", true, OUT, timeout, false, &clock).unwrap();
+        assert_eq!(v.message(), "Running on test 4");
+        assert_eq!(clock.now(), start + Duration::new(6, 0));
+        assert_eq!(
+            verdict_exit_code(&test_cf(), &v, true),
+            POLL_TIMEOUT_EXIT_CODE
+        );
+
+        // Judged before the deadline.
+        let mut src = FakeSource::new([running(1), accepted()]);
+        let v = poll_or_query_verdict(&mut src, "42", true, OUT, timeout, false, &clock).unwrap();
+        assert_eq!(verdict_exit_code(&test_cf(), &v, true), 0);
+    }
+
+    #[test]
+    fn test_poll_timeout_unavailable() {
+        let clock = FakeClock::new();
+        let timeout = Some(Duration::new(3, 0));
+        let mut src = FakeSource::new([running(1), unavailable(), unavailable()]);
+        let v = poll_or_query_verdict(&mut src, "42", true, OUT, timeout, false, &clock).unwrap();
+        assert_eq!(v.message(), "Running on test 1");
+        assert_eq!(
+            verdict_exit_code(&test_cf(), &v, true),
+            POLL_TIMEOUT_EXIT_CODE
+        );
+
+        // Never got a verdict before the deadline.
+        let mut src = FakeSource::new((0..3).map(|_| unavailable()));
+        let v = poll_or_query_verdict(&mut src, "42", true, OUT, timeout, false, &clock).unwrap();
+        assert!(v.is_waiting());
+        assert_eq!(
+            verdict_exit_code(&test_cf(), &v, true),
+            POLL_TIMEOUT_EXIT_CODE
+        );
 
-    if need_poll {
-        poll_or_query_verdict(&mut cf, true, no_color);
+        // Not polling, so there's no deadline to wait for.
+        let mut src = FakeSource::new([unavailable()]);
+        let r = poll_or_query_verdict(&mut src, "42", false, OUT, timeout, false, &clock);
+        assert!(r.is_err());
     }
 }
@@ -0,0 +1,81 @@
+// Decoding HTML character references in the text from Codeforces pages
+
+use std::fmt;
+
+/// Display the wrapped HTML text with the character references decoded.
+pub struct Unescape<'a>(pub &'a str);
+
+const ENTITIES: &[(&str, char)] = &[
+    ("&gt;", '>'),
+    ("&lt;", '<'),
+    ("&amp;", '&'),
+    ("&quot;", '"'),
+];
+
+/// Decode a numeric character reference like `&#8217;` or `&#x2026;` at
+/// the beginning of `s`, return the character and the length of the
+/// reference.
+fn numeric_ref(s: &str) -> Option<(char, usize)> {
+    let body = s.strip_prefix("&#")?;
+    let end = body.find(';')?;
+    let num = &body[..end];
+    let code = match num.strip_prefix(['x', 'X']) {
+        Some(hex) if !hex.is_empty() && hex.bytes().all(|c| c.is_ascii_hexdigit()) => {
+            u32::from_str_radix(hex, 16).ok()?
+        }
+        Some(_) => return None,
+        None if !num.is_empty() && num.bytes().all(|c| c.is_ascii_digit()) => num.parse().ok()?,
+        None => return None,
+    };
+    Some((char::from_u32(code)?, end + 3))
+}
+
+impl fmt::Display for Unescape<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut s = self.0;
+        while let Some(pos) = s.find('&') {
+            f.write_str(&s[..pos])?;
+            s = &s[pos..];
+            let known = ENTITIES
+                .iter()
+                .find(|(e, _)| s.starts_with(e))
+                .map(|(e, c)| (*c, e.len()))
+                .or_else(|| numeric_ref(s));
+            match known {
+                Some((c, len)) => {
+                    write!(f, "{}", c)?;
+                    s = &s[len..];
+                }
+                None => {
+                    // Not a reference we know, keep it as is.
+                    f.write_str("&")?;
+                    s = &s[1..];
+                }
+            }
+        }
+        f.write_str(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unescape() {
+        let s = Unescape("a &lt; b &amp;&amp; c &gt; &quot;d&quot; &#39;e&#39;");
+        assert_eq!(s.to_string(), "a < b && c > \"d\" 'e'");
+        assert_eq!(Unescape("&nbsp; & &amp").to_string(), "&nbsp; & &amp");
+    }
+
+    #[test]
+    fn test_unescape_numeric() {
+        assert_eq!(Unescape("it&#8217;s").to_string(), "it\u{2019}s");
+        assert_eq!(Unescape("wait&#x2026;&#X41;").to_string(), "wait\u{2026}A");
+        assert_eq!(Unescape("&#60;&#62;").to_string(), "<>");
+        // Invalid references are kept.
+        for s in ["&#;", "&#x;", "&#12a;", "&#xD800;", "&#1234567890;", "&#65"] {
+            assert_eq!(Unescape(s).to_string(), s);
+        }
+    }
+}